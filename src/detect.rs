@@ -0,0 +1,145 @@
+use async_trait::async_trait;
+use serde::{Serialize, Deserialize};
+use std::fmt;
+use std::time::Duration;
+
+use crate::{Client, Miner};
+
+/// The miner families detection can identify
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+pub enum MinerKind {
+    Antminer,
+    Avalon,
+    /// 2 fan Minervas running the custom interface
+    Minerva,
+    /// 4 fan Minervas running Minera
+    Minera,
+    Whatsminer,
+    /// Anything else answering the cgminer socket API
+    GenericCgminer,
+    /// A backend registered with ClientBuilder::register_backend, by name
+    Custom(String),
+}
+
+/// The probe that identified a miner
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum DetectionProbe {
+    /// A type section in the cgminer socket stats response
+    SocketStats,
+    /// The cgminer socket answered but nothing more specific matched
+    SocketGeneric,
+    /// Whatsminer's invalid command error on the cgminer socket
+    SocketWhatsminerStatus,
+    /// GET /index.php, after the socket identified a Minerva, tells the two interfaces apart
+    MineraIndex,
+    /// Digest auth challenge on the web interface
+    HttpDigest,
+    /// "Avalon Device" page title
+    AvalonTitle,
+    /// Minerva umi page title
+    MinervaTitle,
+    /// /index.php/app/stats readable without logging in
+    MineraStats,
+    /// WhatsMiner title on /cgi-bin/luci
+    WhatsminerLuci,
+    /// A detector registered with ClientBuilder::register_backend or register_fallback_backend
+    Registered,
+}
+
+/// How a miner was detected, for debugging detection in the field
+#[derive(Debug, Serialize, Clone)]
+pub struct DetectionReport {
+    pub kind: MinerKind,
+    pub probe: DetectionProbe,
+    /// Time from the first probe to the match
+    pub latency: Duration,
+    /// The response or header that matched, truncated
+    pub evidence: String,
+    /// Why the probes tried before the match failed
+    pub failures: Vec<String>,
+}
+
+/// Detection probe for a backend registered with ClientBuilder::register_backend
+/// Use Client::send_recv and Client::http_client to talk to the host
+#[async_trait]
+pub trait Detector: Send + Sync {
+    /// Some with the evidence if the host is this backend's miner
+    async fn detect(&self, client: &Client, ip: &str, port: u16) -> Option<String>;
+}
+
+pub(crate) type Constructor = dyn Fn(Client, String, u16) -> Box<dyn Miner + Send + Sync> + Send + Sync;
+
+/// A downstream Miner implementation and how to find it
+pub(crate) struct Backend {
+    pub name: String,
+    /// Tried only once the built in probes found nothing
+    pub fallback: bool,
+    pub detector: Box<dyn Detector>,
+    pub constructor: Box<Constructor>,
+}
+
+impl fmt::Debug for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Backend").field("name", &self.name).finish()
+    }
+}
+
+/// Longest evidence kept in a report, stats responses run to several KB
+const MAX_EVIDENCE: usize = 512;
+
+pub(crate) fn evidence_from(raw: &str) -> String {
+    match raw.char_indices().nth(MAX_EVIDENCE) {
+        Some((i, _)) => format!("{}...", &raw[..i]),
+        None => raw.to_string(),
+    }
+}
+
+/// Restrict what detection tries
+#[derive(Debug, Clone, Default)]
+pub struct DetectOptions {
+    vendors: Option<Vec<MinerKind>>,
+    skip_socket: bool,
+}
+
+impl DetectOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only report these kinds of miner
+    /// Default is every kind enabled by features
+    pub fn vendors(mut self, vendors: &[MinerKind]) -> Self {
+        self.vendors = Some(vendors.to_vec());
+        self
+    }
+
+    /// Skip the cgminer socket probe and go straight to HTTP
+    /// Useful where the socket API is known to deadlock, such as Minera
+    pub fn skip_socket(mut self, skip: bool) -> Self {
+        self.skip_socket = skip;
+        self
+    }
+
+    pub(crate) fn allows(&self, kind: &MinerKind) -> bool {
+        self.vendors.as_ref().map_or(true, |vendors| vendors.contains(kind))
+    }
+
+    pub(crate) fn skips_socket(&self) -> bool {
+        self.skip_socket
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_filters_and_truncates() {
+        let options = DetectOptions::new().vendors(&[MinerKind::Antminer]);
+        assert!(options.allows(&MinerKind::Antminer));
+        assert!(!options.allows(&MinerKind::Whatsminer));
+        assert!(DetectOptions::new().allows(&MinerKind::Custom("inhouse".into())));
+        assert_eq!(evidence_from("short"), "short");
+        assert_eq!(evidence_from(&"x".repeat(600)).len(), MAX_EVIDENCE + 3);
+    }
+}
@@ -60,4 +60,10 @@ pub enum Error {
     InvalidResponse,
     #[error("Unknown model {0}")]
     UnknownModel(String),
+    #[error("Miner is not hashing")]
+    NotHashing,
+
+    // Caller errors
+    #[error("Invalid argument: {0}")]
+    InvalidArgument(String),
 }
@@ -2,8 +2,14 @@ mod util;
 pub mod miners;
 mod miner;
 
-pub use miner::{Miner, Pool};
+pub use miner::{check_mac_confirmation, export_common, import_common, pool_slots, validate_pools, PoolSlot, BoardTuning, Capability, Environment, FanConfig, FaultCategory, FaultSeverity, Hashboard, LogEntry, LogLevel, Miner, MinerConfig, MinerFault, MinerSnapshot, NetworkConfig, Pool, PoolScheme, PoolStats, PoolStatus, PoolUrl, PsuInfo, ShareStats, Tuning, TuningConfig, UpgradeStage, WorkMode};
+pub use units::{Efficiency, Hashrate, Power, Temperature};
 pub mod error;
+pub mod units;
+mod scan;
+pub use scan::{Scan, ScanItem, ScanRange};
+mod detect;
+pub use detect::{DetectOptions, DetectionProbe, DetectionReport, Detector, MinerKind};
 
 use miners::*;
 use error::Error;
@@ -13,8 +19,15 @@ use serde_json::json;
 use tracing::{debug, instrument};
 pub use tokio::time::Duration;
 use lazy_regex::regex;
-use std::sync::Arc;
-use tokio::sync::Semaphore;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{mpsc, OwnedSemaphorePermit, Semaphore};
+use std::net::IpAddr;
+use tokio::time::Instant;
+use detect::{evidence_from, Backend, Constructor};
+
+/// The kind of miner detected, the probe that matched and its evidence
+type Detected = (MinerKind, DetectionProbe, String);
 
 /*
  * Cgminer socket API has a tendency to fail often but is generally universal
@@ -25,6 +38,11 @@ pub struct ClientBuilder {
     connect_timeout: Duration,
     request_timeout: Duration,
     max_connections: usize,
+    scan_concurrency: usize,
+    probe_timeout: Duration,
+    scan_port: u16,
+    detection_cache: bool,
+    backends: Vec<Backend>,
 }
 
 impl ClientBuilder {
@@ -33,6 +51,11 @@ impl ClientBuilder {
             connect_timeout: Duration::from_secs(5),
             request_timeout: Duration::from_secs(10),
             max_connections: 0,
+            scan_concurrency: 256,
+            probe_timeout: Duration::from_secs(1),
+            scan_port: 4028,
+            detection_cache: true,
+            backends: vec![],
         }
     }
 
@@ -57,6 +80,66 @@ impl ClientBuilder {
         self
     }
 
+    /// Set the number of hosts Client::scan probes at once
+    /// Default is 256
+    pub fn scan_concurrency(mut self, max: usize) -> Self {
+        self.scan_concurrency = max.max(1);
+        self
+    }
+
+    /// Set the connect timeout for the port check Client::scan does before detection
+    /// Default is 1 second
+    pub fn probe_timeout(mut self, timeout: Duration) -> Self {
+        self.probe_timeout = timeout;
+        self
+    }
+
+    /// Set the API port Client::scan probes and detects on, HTTP is always probed on 80
+    /// Default is 4028
+    pub fn scan_port(mut self, port: u16) -> Self {
+        self.scan_port = port;
+        self
+    }
+
+    /// Remember what get_miner detected at each address so later calls skip detection
+    /// Entries are never checked against the miner, see Client::get_miner
+    /// Default is enabled, use Client::forget when a miner is replaced
+    pub fn detection_cache(mut self, enabled: bool) -> Self {
+        self.detection_cache = enabled;
+        self
+    }
+
+    /// Add a Miner implementation from outside this crate
+    /// Registered detectors run in registration order before the built in ones, so they can claim modified firmware of a known vendor
+    /// That puts their requests in front of every detection that misses the cache, stock miners included,
+    /// so keep them cheap or use register_fallback_backend
+    /// The backend is reported and cached as MinerKind::Custom(name)
+    pub fn register_backend<D, F>(self, name: &str, detector: D, constructor: F) -> Self
+        where D: Detector + 'static,
+              F: Fn(Client, String, u16) -> Box<dyn Miner + Send + Sync> + Send + Sync + 'static
+    {
+        self.add_backend(name, Box::new(detector), Box::new(constructor), false)
+    }
+
+    /// Like register_backend, but the detector only runs once the built in probes found nothing,
+    /// ahead of the generic cgminer fallback, so stock miners cost nothing extra
+    pub fn register_fallback_backend<D, F>(self, name: &str, detector: D, constructor: F) -> Self
+        where D: Detector + 'static,
+              F: Fn(Client, String, u16) -> Box<dyn Miner + Send + Sync> + Send + Sync + 'static
+    {
+        self.add_backend(name, Box::new(detector), Box::new(constructor), true)
+    }
+
+    fn add_backend(mut self, name: &str, detector: Box<dyn Detector>, constructor: Box<Constructor>, fallback: bool) -> Self {
+        self.backends.push(Backend {
+            name: name.to_string(),
+            fallback,
+            detector,
+            constructor,
+        });
+        self
+    }
+
     pub fn build(self) -> Result<Client, Error> {
         let client = reqwest::ClientBuilder::new()
             .user_agent("libminer/0.1")
@@ -79,6 +162,11 @@ impl ClientBuilder {
             connect_timeout: self.connect_timeout,
             request_timeout: self.request_timeout,
             lock,
+            scan_concurrency: self.scan_concurrency,
+            probe_timeout: self.probe_timeout,
+            scan_port: self.scan_port,
+            backends: Arc::new(self.backends),
+            detected: if self.detection_cache { Some(Arc::new(Mutex::new(HashMap::new()))) } else { None },
         })
     }
 }
@@ -89,11 +177,17 @@ pub struct Client {
     connect_timeout: Duration,
     request_timeout: Duration,
     lock: Option<Arc<Semaphore>>,
+    scan_concurrency: usize,
+    probe_timeout: Duration,
+    scan_port: u16,
+    backends: Arc<Vec<Backend>>,
+    /// Detection cache keyed by ip:port, shared between clones
+    detected: Option<Arc<Mutex<HashMap<String, MinerKind>>>>,
 }
 
 impl Client {
     /// Connect to a given host with the timeout specified
-    async fn connect(&self, ip: &str, port: u16) -> Result<TcpStream, Error> {
+    pub(crate) async fn connect(&self, ip: &str, port: u16) -> Result<TcpStream, Error> {
         match tokio::time::timeout(
             self.connect_timeout,
             TcpStream::connect(format!("{}:{}", ip, port))
@@ -103,8 +197,13 @@ impl Client {
         }
     }
 
+    /// The HTTP client, configured with this client's timeouts, for registered detectors
+    pub fn http_client(&self) -> &reqwest::Client {
+        &self.http_client
+    }
+
     /// Connect to a host and send data return data as String, close connection after request
-    async fn send_recv<T>(&self, ip: &str, port: u16, data: &T) -> Result<String, Error> 
+    pub async fn send_recv<T>(&self, ip: &str, port: u16, data: &T) -> Result<String, Error> 
         where T: ToString
     {
         let mut stream = self.connect(ip, port).await?;
@@ -125,6 +224,27 @@ impl Client {
         }
     }
 
+    /// Wait for a host to drop off after a reboot then come back up on the given port
+    pub(crate) async fn wait_for_reboot(&self, ip: &str, port: u16) -> Result<(), Error> {
+        let poll = Duration::from_secs(5);
+        // Give the miner a minute to go down, it may have already rebooted before we got here
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(60);
+        while tokio::time::Instant::now() < deadline {
+            if self.connect(ip, port).await.is_err() {
+                break;
+            }
+            tokio::time::sleep(poll).await;
+        }
+        let deadline = tokio::time::Instant::now() + miner::UPGRADE_TIMEOUT;
+        while tokio::time::Instant::now() < deadline {
+            if self.connect(ip, port).await.is_ok() {
+                return Ok(());
+            }
+            tokio::time::sleep(poll).await;
+        }
+        Err(Error::Timeout)
+    }
+
     /// Send data over a websocket to a host
     async fn send<T>(&self, ip: &str, port: u16, data: &T) -> Result<(), Error> 
         where T: ToString
@@ -147,203 +267,633 @@ impl Client {
     /// NOTES:
     /// * On Minervas using the Minera interface, the cgminer API can be deadlocked
     /// * On Whatsminers, the socket API can be responsive but btminer deadlocked, this results in detection successful but every call failing
-    async fn socket_detect(&self, ip: &str, port: u16) -> Result<Box<dyn Miner + Send + Sync>, Error> {
+    ///
+    /// Returns None with the reason pushed to failures when nothing matched, reached is set if the socket answered at all
+    /// A cgminer host of unrecognised type is left in fallback, to be used if HTTP detection finds nothing better
+    #[cfg_attr(not(feature = "generic"), allow(unused_variables))]
+    async fn socket_detect(&self, ip: &str, port: u16, options: &DetectOptions, failures: &mut Vec<String>, reached: &mut bool, fallback: &mut Option<Detected>) -> Option<Detected> {
         debug!("Trying socket detection...");
-        match self.send_recv(ip, port, &json!({"command": "stats"})).await {
-            Ok(resp) => {
-                debug!("Received response from socket API...");
-                if let Ok(stats_resp) = serde_json::from_str::<common::StatsResp>(&resp) {
-                    debug!("Received valid cgminer response.");
-                    if stats_resp.status[0].status != common::StatusCode::SUCC {
-                        return Err(Error::ApiCallFailed(stats_resp.status[0].msg.clone()));
-                    }
-                    if let Some(stats) = stats_resp.stats {
-                        debug!("Checking for type in stats response...");
-                        for stat in stats {
-                            match stat {
-                                #[cfg(feature = "antminer")]
-                                common::Stats::AmVersion(_) => {
-                                    debug!("Found Antminer miner at {}", ip);
-                                    return Ok(Box::new(antminer::Antminer::new(self.clone(), ip.into(), port)));
-                                },
-                                #[cfg(feature = "avalon")]
-                                common::Stats::AvaStats(_) => {
-                                    debug!("Found Avalon miner at {}", ip);
-                                    return Ok(Box::new(avalon::Avalon::new(self.clone(), ip.into(), port)));
-                                },
-                                #[cfg(feature = "minerva")]
-                                common::Stats::Dev(stat) => {
-                                    if let Some(type_) = stat.type_ {
-                                        if type_ == "Minerva" {
-                                            // We need to differentiate between the 2 interfaces
-                                            // easiest thing is to send a GET request to /index.php
-                                            // If we get a 200, we know its running minera
-                                            debug!("Found Minerva, determining interface...");
-                                            let resp2 = self.http_client
-                                                .get(&format!("http://{}/index.php", ip))
-                                                .send()
-                                                .await?;
-                                            return match resp2.status() {
-                                                reqwest::StatusCode::NOT_FOUND => {
-                                                    debug!("Found Minerva (Custom Interface) at {}", ip);
-                                                    Ok(Box::new(minerva::Minerva::new(self.clone(), ip.into(), port)))
-                                                }
-                                                reqwest::StatusCode::OK => {
-                                                    debug!("Found Minerva (Minera Interface) at {}", ip);
-                                                    Ok(Box::new(minerva::Minera::new(self.clone(), ip.into(), port)))
-                                                }
-                                                _ => {
-                                                    debug!("Unable to determine interface for Minerva at {}", ip);
-                                                    Err(Error::UnknownMinerType)
-                                                },
-                                            };
-                                        } else {
-                                            debug!("Unsupported miner type: {} at {}", type_, ip);
-                                            return Err(Error::UnknownMinerType);
-                                        }
-                                    } else {
-                                        debug!("Miner did not include type in response at {}", ip);
-                                        return Err(Error::UnknownMinerType);
+        let resp = match self.send_recv(ip, port, &json!({"command": "stats"})).await {
+            Ok(resp) => resp,
+            Err(e) => {
+                debug!("Error while sending request to socket API: {}", e);
+                failures.push(format!("socket: {}", e));
+                return None;
+            }
+        };
+        *reached = true;
+        debug!("Received response from socket API...");
+        if let Ok(stats_resp) = serde_json::from_str::<common::StatsResp>(&resp) {
+            debug!("Received valid cgminer response.");
+            if stats_resp.status[0].status != common::StatusCode::SUCC {
+                failures.push(format!("socket: stats failed: {}", stats_resp.status[0].msg));
+                return None;
+            }
+            let stats = match stats_resp.stats {
+                Some(stats) => stats,
+                None => {
+                    debug!("Unable to parse stats response at {}\n{}", ip, resp);
+                    failures.push("socket: stats response has no STATS section".to_string());
+                    return None;
+                }
+            };
+            debug!("Checking for type in stats response...");
+            #[cfg_attr(not(feature = "minerva"), allow(unused_mut))]
+            let mut unrecognised = None;
+            for stat in stats {
+                match stat {
+                    #[cfg(feature = "antminer")]
+                    common::Stats::AmVersion(_) if options.allows(&MinerKind::Antminer) => {
+                        debug!("Found Antminer miner at {}", ip);
+                        return Some((MinerKind::Antminer, DetectionProbe::SocketStats, evidence_from(&resp)));
+                    },
+                    #[cfg(feature = "avalon")]
+                    common::Stats::AvaStats(_) if options.allows(&MinerKind::Avalon) => {
+                        debug!("Found Avalon miner at {}", ip);
+                        return Some((MinerKind::Avalon, DetectionProbe::SocketStats, evidence_from(&resp)));
+                    },
+                    #[cfg(feature = "minerva")]
+                    common::Stats::Dev(stat) => {
+                        match stat.type_.as_deref() {
+                            Some("Minerva") => {
+                                // We need to differentiate between the 2 interfaces
+                                // easiest thing is to send a GET request to /index.php
+                                // If we get a 200, we know its running minera
+                                debug!("Found Minerva, determining interface...");
+                                let status = match self.http_client.get(&format!("http://{}/index.php", ip)).send().await {
+                                    Ok(resp) => resp.status(),
+                                    Err(e) => {
+                                        failures.push(format!("socket: Minerva found but /index.php failed: {}", e));
+                                        return None;
+                                    }
+                                };
+                                let evidence = format!("GET /index.php returned {}", status);
+                                match status {
+                                    reqwest::StatusCode::NOT_FOUND if options.allows(&MinerKind::Minerva) => {
+                                        debug!("Found Minerva (Custom Interface) at {}", ip);
+                                        return Some((MinerKind::Minerva, DetectionProbe::MineraIndex, evidence));
+                                    }
+                                    reqwest::StatusCode::OK if options.allows(&MinerKind::Minera) => {
+                                        debug!("Found Minerva (Minera Interface) at {}", ip);
+                                        return Some((MinerKind::Minera, DetectionProbe::MineraIndex, evidence));
                                     }
+                                    reqwest::StatusCode::NOT_FOUND | reqwest::StatusCode::OK => {
+                                        failures.push(format!("socket: Minerva excluded by options, {}", evidence));
+                                    }
+                                    _ => {
+                                        debug!("Unable to determine interface for Minerva at {}", ip);
+                                        failures.push(format!("socket: unable to determine Minerva interface, {}", evidence));
+                                    },
                                 }
-                                _ => {} // We don't care about the other stats
+                                return None;
                             }
-                        }
-                        debug!("Stats did not include a section containing type at {}\n{}", ip, resp);
-                        return Err(Error::UnknownMinerType);
-                    } else {
-                        debug!("Unable to parse stats response at {}\n{}", ip, resp);
-                        return Err(Error::UnknownMinerType);
-                    }
-                } else if let Ok(status) = serde_json::from_str::<common::Status>(&resp) {
-                    // Whatsminer returns just the cgminer status error with invalid json and a description containing whatsminer
-                    // {"STATUS":"E","When":"0","Code":23,"Msg":"Invalid JSON","Description":"whatsminer"}
-                    //TODO: Don't hardcode the status code for Invalid Command
-                    #[cfg(feature = "whatsminer")]
-                    if status.status == common::StatusCode::ERROR && status.code == Some(14) {
-                        // lowercase and regex the description for "whatsminer"
-                        if let Some(desc) = status.description {
-                            if desc.to_lowercase().contains("whatsminer") {
-                                debug!("Found Whatsminer at {}", ip);
-                                return Ok(Box::new(whatsminer::Whatsminer::new(self.clone(), ip.into(), port)));
+                            Some(type_) => {
+                                debug!("Unsupported miner type: {} at {}", type_, ip);
+                                unrecognised = Some(format!("socket: unsupported miner type {}", type_));
+                                break;
+                            }
+                            None => {
+                                debug!("Miner did not include type in response at {}", ip);
+                                unrecognised = Some("socket: stats did not include a type".to_string());
+                                break;
                             }
                         }
                     }
-                    debug!("Received error response but not whatsminer at {}\n{}", ip, resp);
-                    return Err(Error::UnknownMinerType);
-                } else {
-                    debug!("Unable to parse response from socket API: {}", resp);
-                    return Err(Error::UnknownMinerType);
+                    _ => {} // We don't care about the other stats, or the vendor was excluded
                 }
             }
-            Err(e) => {
-                debug!("Error while sending request to socket API: {}", e);
-                return Err(Error::UnknownMinerType);
+            debug!("Stats did not identify the miner at {}\n{}", ip, resp);
+            failures.push(unrecognised.unwrap_or_else(|| "socket: stats did not identify an allowed miner".to_string()));
+            #[cfg(feature = "generic")]
+            if options.allows(&MinerKind::GenericCgminer) {
+                *fallback = Some((MinerKind::GenericCgminer, DetectionProbe::SocketGeneric, evidence_from(&resp)));
+            }
+        } else if let Ok(status) = serde_json::from_str::<common::Status>(&resp) {
+            // Whatsminer returns just the cgminer status error with invalid json and a description containing whatsminer
+            // {"STATUS":"E","When":"0","Code":23,"Msg":"Invalid JSON","Description":"whatsminer"}
+            //TODO: Don't hardcode the status code for Invalid Command
+            #[cfg(feature = "whatsminer")]
+            if status.status == common::StatusCode::ERROR && status.code == Some(14) && options.allows(&MinerKind::Whatsminer) {
+                // lowercase and regex the description for "whatsminer"
+                if let Some(desc) = &status.description {
+                    if desc.to_lowercase().contains("whatsminer") {
+                        debug!("Found Whatsminer at {}", ip);
+                        return Some((MinerKind::Whatsminer, DetectionProbe::SocketWhatsminerStatus, evidence_from(&resp)));
+                    }
+                }
             }
+            debug!("Received error response but not whatsminer at {}\n{}", ip, resp);
+            failures.push(format!("socket: error response: {}", status.msg));
+        } else {
+            debug!("Unable to parse response from socket API: {}", resp);
+            failures.push(format!("socket: unparseable response: {}", evidence_from(&resp)));
         }
+        None
     }
 
-    async fn http_detect(&self, ip: &str, port: u16) -> Result<Box<dyn Miner + Send + Sync>, Error> {
+    /// Returns None with the reason pushed to failures when nothing matched, reached is set if the web interface answered at all
+    #[cfg_attr(not(feature = "whatsminer"), allow(unused_variables))]
+    async fn http_detect(&self, ip: &str, port: u16, options: &DetectOptions, failures: &mut Vec<String>, reached: &mut bool) -> Option<Detected> {
         debug!("Trying HTTP detection...");
         // To reduce traffic and since detection is entirely on status response, we can just send a HEAD request
-        // Start with Antminer, if this fails to connect return a timeout
-        match self.http_client.head(&format!("http://{}/", ip)).send().await {
-            Ok(resp) => {
-                debug!("Received response from HTTP API...");
-                //TODO: In theory we could probably do this with a single request
-                #[cfg(feature = "antminer")]
-                if resp.status() == reqwest::StatusCode::UNAUTHORIZED {
-                    if let Some(auth) = resp.headers().get("WWW-Authenticate") {
-                        let re = regex!(r"^[Dd]igest");
-                        if re.is_match(auth.to_str().unwrap()) {
-                            debug!("Found Antminer at {}", ip);
-                            return Ok(Box::new(antminer::Antminer::new(self.clone(), ip.into(), port)));
-                        }
-                    }
+        // Start with Antminer, if this fails to connect there's nothing to detect
+        let resp = match self.http_client.head(&format!("http://{}/", ip)).send().await {
+            Ok(resp) => resp,
+            Err(e) => {
+                debug!("Error while sending request to HTTP API: {}", e);
+                failures.push(format!("http: {}", e));
+                return None;
+            }
+        };
+        *reached = true;
+        let root_status = resp.status();
+        debug!("Received response from HTTP API...");
+        //TODO: In theory we could probably do this with a single request
+        #[cfg(feature = "antminer")]
+        if resp.status() == reqwest::StatusCode::UNAUTHORIZED && options.allows(&MinerKind::Antminer) {
+            if let Some(auth) = resp.headers().get("WWW-Authenticate").and_then(|auth| auth.to_str().ok()) {
+                let re = regex!(r"^[Dd]igest");
+                if re.is_match(auth) {
+                    debug!("Found Antminer at {}", ip);
+                    return Some((MinerKind::Antminer, DetectionProbe::HttpDigest, evidence_from(auth)));
                 }
-                #[cfg(feature = "avalon")]
-                {
-                    let re = regex!(r"<title>Avalon Device</title>");
-                    if re.is_match(&resp.text().await?) {
-                        debug!("Found Avalon at {}", ip);
-                        return Ok(Box::new(avalon::Avalon::new(self.clone(), ip.into(), port)));
-                    }
+            }
+        }
+        #[cfg(feature = "avalon")]
+        if options.allows(&MinerKind::Avalon) {
+            let re = regex!(r"<title>Avalon Device</title>");
+            if let Ok(text) = resp.text().await {
+                if let Some(title) = re.find(&text) {
+                    debug!("Found Avalon at {}", ip);
+                    return Some((MinerKind::Avalon, DetectionProbe::AvalonTitle, title.as_str().to_string()));
                 }
-                #[cfg(feature = "minerva")]
-                {
-                    // 2 fan minervas have the title Minerva and are based off umi
-                    debug!("Checking for custom Minerva...");
-                    let re = regex!(r"Minerva(.|\n)+umi");
-                    let resp = self.http_client.get(&format!("https://{}", ip)).send().await;
-                    if let Ok(resp) = resp {
-                        let text = resp.text().await?;
-                        if re.is_match(&text) {
+            }
+        }
+        #[cfg(feature = "minerva")]
+        {
+            // 2 fan minervas have the title Minerva and are based off umi
+            if options.allows(&MinerKind::Minerva) {
+                debug!("Checking for custom Minerva...");
+                let re = regex!(r"Minerva(.|\n)+umi");
+                if let Ok(resp) = self.http_client.get(&format!("https://{}", ip)).send().await {
+                    if let Ok(text) = resp.text().await {
+                        if let Some(m) = re.find(&text) {
                             debug!("Found Minerva (Custom Interface) at {}", ip);
-                            return Ok(Box::new(minerva::Minerva::new(self.clone(), ip.into(), port)));
+                            return Some((MinerKind::Minerva, DetectionProbe::MinervaTitle, evidence_from(m.as_str())));
                         }
                     }
+                }
+            }
 
-                    // 4 fan minervas permit a request to /index.php/app/stats even when not logged in
-                    debug!("Checking for minera Minerva...");
-                    let resp = self.http_client.head(&format!("http://{}/index.php/app/stats", ip)).send().await?;
-                    if resp.status() == reqwest::StatusCode::OK {
+            // 4 fan minervas permit a request to /index.php/app/stats even when not logged in
+            if options.allows(&MinerKind::Minera) {
+                debug!("Checking for minera Minerva...");
+                match self.http_client.head(&format!("http://{}/index.php/app/stats", ip)).send().await {
+                    Ok(resp) if resp.status() == reqwest::StatusCode::OK => {
                         debug!("Found Minerva at {}", ip);
-                        return Ok(Box::new(minerva::Minera::new(self.clone(), ip.into(), port)));
-                    }
+                        return Some((MinerKind::Minera, DetectionProbe::MineraStats, "HEAD /index.php/app/stats returned 200 OK".to_string()));
+                    },
+                    Ok(_) => {},
+                    Err(e) => failures.push(format!("http: /index.php/app/stats: {}", e)),
                 }
-                
-                #[cfg(feature = "whatsminer")]
-                {
-                    // Lastly check whatsminers, /cgi-bin/luci and look for whatsminer in the body
-                    debug!("Checking for Whatsminer...");
-                    let resp = self.http_client.get(&format!("http://{}/cgi-bin/luci", ip)).send().await?;
-                    if resp.status() == reqwest::StatusCode::FORBIDDEN {
-                        let re = regex!(r"<title>WhatsMiner");
-                        if re.is_match(&resp.text().await?) {
-                            debug!("Detected Whatsminer at {}:{}", ip, port);
-                            //warn!("Socket API did not respond, this miner may not work.");
-                            return Ok(Box::new(whatsminer::Whatsminer::new(self.clone(), ip.to_string(), port)));
-                        }
+            }
+        }
+
+        #[cfg(feature = "whatsminer")]
+        if options.allows(&MinerKind::Whatsminer) {
+            // Lastly check whatsminers, /cgi-bin/luci and look for whatsminer in the body
+            debug!("Checking for Whatsminer...");
+            match self.http_client.get(&format!("http://{}/cgi-bin/luci", ip)).send().await {
+                Ok(resp) if resp.status() == reqwest::StatusCode::FORBIDDEN => {
+                    let re = regex!(r"<title>WhatsMiner");
+                    if let Some(title) = resp.text().await.ok().as_deref().and_then(|text| re.find(text)) {
+                        debug!("Detected Whatsminer at {}:{}", ip, port);
+                        //warn!("Socket API did not respond, this miner may not work.");
+                        return Some((MinerKind::Whatsminer, DetectionProbe::WhatsminerLuci, title.as_str().to_string()));
                     }
-                }
+                },
+                Ok(_) => {},
+                Err(e) => failures.push(format!("http: /cgi-bin/luci: {}", e)),
+            }
+        }
 
-                debug!("Unable to determine miner type {}", ip);
-                Err(Error::UnknownMinerType)
+        debug!("Unable to determine miner type {}", ip);
+        failures.push(format!("http: no probe matched, / returned {}", root_status));
+        None
+    }
+
+    /// Run the registered detectors that go before or after the built in probes, in registration order
+    async fn registered_detect(&self, ip: &str, port: u16, options: &DetectOptions, fallback: bool, failures: &mut Vec<String>, reached: &mut bool) -> Option<Detected> {
+        for backend in self.backends.iter().filter(|backend| backend.fallback == fallback) {
+            let kind = MinerKind::Custom(backend.name.clone());
+            if !options.allows(&kind) {
+                continue;
             }
-            Err(e) => {
-                debug!("Error while sending request to HTTP API: {}", e);
-                Err(Error::Timeout)
+            match backend.detector.detect(self, ip, port).await {
+                Some(evidence) => {
+                    debug!("Found {} at {}", backend.name, ip);
+                    *reached = true;
+                    return Some((kind, DetectionProbe::Registered, evidence_from(&evidence)));
+                },
+                None => failures.push(format!("{}: no match", backend.name)),
             }
         }
+        None
     }
 
-    /// Detects the type of miner at the given IP and port
+    /// Detects the type of miner at the given IP and port, reporting how it was found
+    /// Fails with UnknownMinerType if the host answered but matched nothing, or Timeout if it never answered
     /// Default port is 4028
     #[instrument]
-    pub async fn get_miner(&self, ip: &str, port: Option<u16>) -> Result<Box<dyn Miner + Send + Sync>, Error> {
+    pub async fn detect(&self, ip: &str, port: Option<u16>, options: &DetectOptions) -> Result<(Box<dyn Miner + Send + Sync>, DetectionReport), Error> {
         let port = port.unwrap_or(4028);
-        let permit = {
-            if let Some(lock) = &self.lock {
-                Some(lock.clone().acquire_owned().await?)
-            } else {
-                None
+        let permit = self.acquire().await?;
+        let report = self.detect_report(ip, port, options).await?;
+        Ok((self.wrap(self.new_miner(&report.kind, ip, port)?, permit), report))
+    }
+
+    /// Run detection and cache the result, the caller holds the connection slot
+    async fn detect_report(&self, ip: &str, port: u16, options: &DetectOptions) -> Result<DetectionReport, Error> {
+        debug!("Detecting miner at {}:{}", ip, port);
+        let start = Instant::now();
+        let mut failures = vec![];
+        let mut reached = false;
+        let mut fallback = None;
+        let mut detected = self.registered_detect(ip, port, options, false, &mut failures, &mut reached).await;
+        if detected.is_none() && !options.skips_socket() {
+            detected = self.socket_detect(ip, port, options, &mut failures, &mut reached, &mut fallback).await;
+        }
+        if detected.is_none() {
+            detected = self.http_detect(ip, port, options, &mut failures, &mut reached).await;
+        }
+        if detected.is_none() {
+            detected = self.registered_detect(ip, port, options, true, &mut failures, &mut reached).await;
+        }
+        if detected.is_none() {
+            detected = fallback;
+        }
+        let (kind, probe, evidence) = match detected {
+            Some(detected) => detected,
+            None => {
+                debug!("Unable to detect miner at {}: {}", ip, failures.join("; "));
+                return Err(if reached { Error::UnknownMinerType } else { Error::Timeout });
+            },
+        };
+        let report = DetectionReport {
+            kind,
+            probe,
+            latency: start.elapsed(),
+            evidence,
+            failures,
+        };
+        if let Some(detected) = &self.detected {
+            detected.lock().unwrap_or_else(|e| e.into_inner()).insert(format!("{}:{}", ip, port), report.kind.clone());
+        }
+        Ok(report)
+    }
+
+    /// Detects the type of miner at the given IP and port, or uses the cached result of an earlier detection
+    /// A cached kind is trusted without contacting the miner, so if the miner at an address is replaced
+    /// by another kind, calls on the returned miner fail until Client::forget is called for it
+    /// Default port is 4028
+    pub async fn get_miner(&self, ip: &str, port: Option<u16>) -> Result<Box<dyn Miner + Send + Sync>, Error> {
+        match self.cached(ip, port.unwrap_or(4028)) {
+            Some(kind) => {
+                debug!("Using cached detection of {:?} at {}", kind, ip);
+                let miner = self.connect_as(kind, ip, port).await;
+                if miner.is_err() {
+                    self.forget(ip, port);
+                }
+                miner
+            },
+            None => Ok(self.detect(ip, port, &DetectOptions::default()).await?.0),
+        }
+    }
+
+    /// Connect to a miner of a known kind without running detection
+    /// Nothing is sent to the miner, so a wrong kind only shows up as failing calls
+    /// Default port is 4028
+    pub async fn connect_as(&self, kind: MinerKind, ip: &str, port: Option<u16>) -> Result<Box<dyn Miner + Send + Sync>, Error> {
+        let permit = self.acquire().await?;
+        Ok(self.wrap(self.new_miner(&kind, ip, port.unwrap_or(4028))?, permit))
+    }
+
+    fn cached(&self, ip: &str, port: u16) -> Option<MinerKind> {
+        self.detected.as_ref().and_then(|detected| {
+            detected.lock().unwrap_or_else(|e| e.into_inner()).get(&format!("{}:{}", ip, port)).cloned()
+        })
+    }
+
+    /// Drop the cached detection for an address, e.g. after a miner is swapped
+    pub fn forget(&self, ip: &str, port: Option<u16>) {
+        if let Some(detected) = &self.detected {
+            detected.lock().unwrap_or_else(|e| e.into_inner()).remove(&format!("{}:{}", ip, port.unwrap_or(4028)));
+        }
+    }
+
+    /// Take a connection slot when max_connections is set
+    async fn acquire(&self) -> Result<Option<OwnedSemaphorePermit>, Error> {
+        match &self.lock {
+            Some(lock) => Ok(Some(lock.clone().acquire_owned().await?)),
+            None => Ok(None),
+        }
+    }
+
+    /// The returned miner holds the connection slot until it's dropped
+    fn wrap(&self, miner: Box<dyn Miner + Send + Sync>, permit: Option<OwnedSemaphorePermit>) -> Box<dyn Miner + Send + Sync> {
+        match permit {
+            Some(permit) => Box::new(miner::LockMiner::new_locked(miner, permit)),
+            None => miner,
+        }
+    }
+
+    /// Build the backend for a kind, NotSupported if its feature isn't enabled or it isn't registered
+    fn new_miner(&self, kind: &MinerKind, ip: &str, port: u16) -> Result<Box<dyn Miner + Send + Sync>, Error> {
+        match kind {
+            MinerKind::Custom(name) => match self.backends.iter().find(|backend| &backend.name == name) {
+                Some(backend) => Ok((backend.constructor)(self.clone(), ip.into(), port)),
+                None => Err(Error::NotSupported),
+            },
+            #[cfg(feature = "antminer")]
+            MinerKind::Antminer => Ok(Box::new(antminer::Antminer::new(self.clone(), ip.into(), port))),
+            #[cfg(feature = "avalon")]
+            MinerKind::Avalon => Ok(Box::new(avalon::Avalon::new(self.clone(), ip.into(), port))),
+            #[cfg(feature = "minerva")]
+            MinerKind::Minerva => Ok(Box::new(minerva::Minerva::new(self.clone(), ip.into(), port))),
+            #[cfg(feature = "minerva")]
+            MinerKind::Minera => Ok(Box::new(minerva::Minera::new(self.clone(), ip.into(), port))),
+            #[cfg(feature = "whatsminer")]
+            MinerKind::Whatsminer => Ok(Box::new(whatsminer::Whatsminer::new(self.clone(), ip.into(), port))),
+            #[cfg(feature = "generic")]
+            MinerKind::GenericCgminer => Ok(Box::new(generic::GenericCgminer::new(self.clone(), ip.into(), port))),
+            #[allow(unreachable_patterns)]
+            _ => Err(Error::NotSupported),
+        }
+    }
+
+    /// Quick check for anything listening on the cgminer or HTTP port, so dead addresses skip full detection
+    async fn probe(&self, ip: IpAddr) -> bool {
+        for port in [self.scan_port, 80] {
+            let connect = TcpStream::connect((ip, port));
+            if let Ok(Ok(_)) = tokio::time::timeout(self.probe_timeout, connect).await {
+                return true;
             }
+        }
+        false
+    }
+
+    /// Like get_miner, but the connection slot is only held while detecting
+    /// A scan yields far more miners than max_connections, callers holding on to them mustn't stall the rest
+    async fn scan_host(&self, ip: &str) -> Result<Box<dyn Miner + Send + Sync>, Error> {
+        let kind = match self.cached(ip, self.scan_port) {
+            Some(kind) => kind,
+            None => {
+                let _permit = self.acquire().await?;
+                self.detect_report(ip, self.scan_port, &DetectOptions::default()).await?.kind
+            },
         };
-        debug!("Detecting miner at {}:{}", ip, port);
-        let miner = {
-            if let Ok(miner) = self.socket_detect(ip, port).await {
-                Ok(miner)
-            } else {
-                self.http_detect(ip, port).await
+        self.new_miner(&kind, ip, self.scan_port)
+    }
+
+    /// Detect miners across the given ranges, probing up to scan_concurrency hosts at once
+    /// Detection respects max_connections, but the miners yielded are not limited by it:
+    /// they don't hold a connection slot and their calls don't wait for one
+    /// To keep to the limit afterwards, reconnect with Client::get_miner, which uses the detection cache
+    /// Must be called from within a tokio runtime
+    pub fn scan(&self, ranges: Vec<ScanRange>) -> Scan {
+        let (tx, rx) = mpsc::channel(self.scan_concurrency);
+        let client = self.clone();
+        tokio::spawn(async move {
+            let limit = Arc::new(Semaphore::new(client.scan_concurrency));
+            for ip in ranges.iter().flat_map(|range| range.hosts()) {
+                let permit = match limit.clone().acquire_owned().await {
+                    Ok(permit) => permit,
+                    Err(_) => break,
+                };
+                if tx.is_closed() {
+                    debug!("Scan dropped, stopping at {}", ip);
+                    break;
+                }
+                let client = client.clone();
+                let tx = tx.clone();
+                tokio::spawn(async move {
+                    let _permit = permit;
+                    if client.probe(ip).await {
+                        let miner = client.scan_host(&ip.to_string()).await;
+                        let _ = tx.send((ip, miner)).await;
+                    }
+                });
             }
-        }?;
-        if let Some(permit) = permit {
-            Ok(Box::new(miner::LockMiner::new_locked(
-                miner,
-                permit,
-            )) as Box<dyn Miner + Send + Sync>)
-        } else {
-            Ok(miner)
+        });
+        Scan { rx }
+    }
+}
+
+#[cfg(all(test, feature = "antminer"))]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+
+    struct AlwaysDetect;
+
+    /// A downstream backend that only implements the required methods
+    struct CoreOnly;
+
+    #[async_trait]
+    impl Miner for CoreOnly {
+        fn new(_client: Client, _ip: String, _port: u16) -> Self {
+            CoreOnly
+        }
+
+        fn get_type(&self) -> &'static str {
+            "CoreOnly"
+        }
+
+        async fn get_model(&self) -> Result<String, Error> {
+            Ok("In-house S1".to_string())
+        }
+
+        async fn auth(&mut self, _username: &str, _password: &str) -> Result<(), Error> {
+            Ok(())
+        }
+
+        async fn reboot(&mut self) -> Result<(), Error> {
+            Ok(())
+        }
+
+        async fn get_hashrate(&self) -> Result<Hashrate, Error> {
+            Ok(Hashrate::from_ghs(100000.0))
+        }
+
+        async fn get_power(&self) -> Result<Power, Error> {
+            Ok(Power::from_watts(3000.0))
+        }
+
+        async fn get_efficiency(&self) -> Result<Efficiency, Error> {
+            Ok(Efficiency::from_j_per_th(30.0))
+        }
+
+        async fn get_nameplate_rate(&self) -> Result<Hashrate, Error> {
+            Ok(Hashrate::from_ghs(100000.0))
+        }
+
+        async fn get_temperature(&self) -> Result<Temperature, Error> {
+            Ok(Temperature::from_celsius(65.0))
+        }
+
+        async fn get_fan_speed(&self) -> Result<Vec<u32>, Error> {
+            Ok(vec![4000, 4000])
+        }
+
+        async fn get_pools(&self) -> Result<Vec<Pool>, Error> {
+            Err(Error::NotSupported)
+        }
+
+        async fn set_pools(&mut self, _pools: Vec<Pool>) -> Result<(), Error> {
+            Err(Error::NotSupported)
+        }
+
+        async fn get_sleep(&self) -> Result<bool, Error> {
+            Ok(false)
+        }
+
+        async fn set_sleep(&mut self, _sleep: bool) -> Result<(), Error> {
+            Ok(())
+        }
+
+        async fn get_blink(&self) -> Result<bool, Error> {
+            Ok(false)
+        }
+
+        async fn set_blink(&mut self, _blink: bool) -> Result<(), Error> {
+            Ok(())
+        }
+
+        async fn get_logs(&mut self) -> Result<Vec<String>, Error> {
+            Ok(vec![])
+        }
+
+        async fn get_mac(&self) -> Result<String, Error> {
+            Ok("00:11:22:33:44:55".to_string())
+        }
+
+        async fn get_errors(&mut self) -> Result<Vec<MinerFault>, Error> {
+            Ok(vec![])
+        }
+    }
+
+    #[async_trait]
+    impl Detector for AlwaysDetect {
+        async fn detect(&self, _client: &Client, _ip: &str, _port: u16) -> Option<String> {
+            Some("in-house firmware".to_string())
+        }
+    }
+
+    /// Stand in for a successful detection
+    fn remember(client: &Client, ip: &str, port: u16, kind: MinerKind) {
+        if let Some(detected) = &client.detected {
+            detected.lock().unwrap().insert(format!("{}:{}", ip, port), kind);
+        }
+    }
+
+    #[test]
+    fn it_forgets_cached_detections() {
+        let client = ClientBuilder::new().build().unwrap();
+        assert_eq!(client.cached("10.0.0.20", 4028), None);
+        remember(&client, "10.0.0.20", 4028, MinerKind::Antminer);
+        remember(&client, "10.0.0.20", 4029, MinerKind::Whatsminer);
+        assert_eq!(client.cached("10.0.0.20", 4028), Some(MinerKind::Antminer));
+
+        // Only the given port is dropped, None means 4028
+        client.forget("10.0.0.20", None);
+        assert_eq!(client.cached("10.0.0.20", 4028), None);
+        assert_eq!(client.cached("10.0.0.20", 4029), Some(MinerKind::Whatsminer));
+
+        let uncached = ClientBuilder::new().detection_cache(false).build().unwrap();
+        remember(&uncached, "10.0.0.20", 4028, MinerKind::Antminer);
+        assert_eq!(uncached.cached("10.0.0.20", 4028), None);
+    }
+
+    #[tokio::test]
+    async fn it_forgets_detections_it_cannot_connect_as() {
+        let client = ClientBuilder::new().build().unwrap();
+        // A backend that is no longer registered
+        remember(&client, "10.0.0.20", 4028, MinerKind::Custom("retired".to_string()));
+        assert!(client.get_miner("10.0.0.20", None).await.is_err());
+        assert_eq!(client.cached("10.0.0.20", 4028), None);
+    }
+
+    #[tokio::test]
+    async fn it_uses_registered_backends() {
+        let client = ClientBuilder::new()
+            .register_backend("inhouse", AlwaysDetect, |client, ip, port| Box::new(antminer::Antminer::new(client, ip, port)))
+            .build()
+            .unwrap();
+        let (miner, report) = client.detect("192.0.2.1", None, &DetectOptions::new()).await.unwrap();
+        assert_eq!(miner.get_type(), "Antminer");
+        assert_eq!(report.kind, MinerKind::Custom("inhouse".to_string()));
+        assert_eq!(report.probe, DetectionProbe::Registered);
+        // Served from the detection cache
+        assert!(client.get_miner("192.0.2.1", None).await.is_ok());
+        assert!(client.connect_as(MinerKind::Custom("unknown".to_string()), "192.0.2.1", None).await.is_err());
+
+        let client = ClientBuilder::new()
+            .register_backend("coreonly", AlwaysDetect, |client, ip, port| Box::new(CoreOnly::new(client, ip, port)))
+            .build()
+            .unwrap();
+        let (mut miner, _) = client.detect("192.0.2.2", None, &DetectOptions::new()).await.unwrap();
+        assert_eq!(miner.get_type(), "CoreOnly");
+        assert!(miner.capabilities().is_empty());
+        assert!(matches!(miner.get_uptime().await, Err(Error::NotSupported)));
+        assert!(matches!(miner.set_tuning(None, TuningConfig::default()).await, Err(Error::NotSupported)));
+        let snapshot = miner.get_snapshot().await.unwrap();
+        assert_eq!(snapshot.model, "In-house S1");
+        assert!(snapshot.pools.is_none());
+        assert_eq!(snapshot.sleep, Some(false));
+        let config = miner.export_config().await.unwrap();
+        assert_eq!(config.vendor, "CoreOnly");
+        miner.import_config(config).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn it_runs_fallback_backends_last() {
+        let constructor = |client, ip, port| Box::new(antminer::Antminer::new(client, ip, port)) as Box<dyn Miner + Send + Sync>;
+        // Registration order doesn't put a fallback ahead of the built in probes or other backends
+        let client = ClientBuilder::new()
+            .register_fallback_backend("late", AlwaysDetect, constructor)
+            .register_backend("early", AlwaysDetect, constructor)
+            .build()
+            .unwrap();
+        let (_, report) = client.detect("192.0.2.1", None, &DetectOptions::new()).await.unwrap();
+        assert_eq!(report.kind, MinerKind::Custom("early".to_string()));
+
+        // Nothing listening, so the built in probes find nothing and the fallback gets its turn
+        let port = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap().port();
+        let client = ClientBuilder::new()
+            .register_fallback_backend("late", AlwaysDetect, constructor)
+            .build()
+            .unwrap();
+        let (_, report) = client.detect("127.0.0.1", Some(port), &DetectOptions::new()).await.unwrap();
+        assert_eq!(report.kind, MinerKind::Custom("late".to_string()));
+    }
+
+    #[tokio::test]
+    async fn it_scans_past_max_connections() {
+        // Anything listening gets past the probe, the registered detector does the rest
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let client = ClientBuilder::new()
+            .max_connections(1)
+            .scan_port(listener.local_addr().unwrap().port())
+            .register_backend("inhouse", AlwaysDetect, |client, ip, port| Box::new(antminer::Antminer::new(client, ip, port)))
+            .build()
+            .unwrap();
+        // The same host three times, each detection needs the single slot while the earlier miners are still held
+        let range: ScanRange = "127.0.0.1".parse().unwrap();
+        let mut scan = client.scan(vec![range; 3]);
+        let mut miners = vec![];
+        while let Ok(Some((_, miner))) = tokio::time::timeout(Duration::from_secs(5), scan.next()).await {
+            miners.push(miner.unwrap());
         }
+        assert_eq!(miners.len(), 3);
     }
 }
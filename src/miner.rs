@@ -1,32 +1,625 @@
 use async_trait::async_trait;
 use serde::{Serialize, Deserialize};
 use lazy_regex::{Regex, Lazy};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use std::fmt;
+use std::net::Ipv4Addr;
+use std::str::FromStr;
+use std::time::Duration;
 use crate::error::Error;
+use crate::units::{Efficiency, Hashrate, Power, Temperature};
 use crate::Client;
+use tracing::warn;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum PoolScheme {
+    StratumTcp,
+    StratumSsl,
+    Stratum2Tcp,
+}
+
+impl PoolScheme {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PoolScheme::StratumTcp => "stratum+tcp",
+            PoolScheme::StratumSsl => "stratum+ssl",
+            PoolScheme::Stratum2Tcp => "stratum2+tcp",
+        }
+    }
+}
+
+/// A parsed stratum URL, a URL without a scheme is taken as stratum+tcp
+/// Parsing only checks the structure, validate() checks it's something a miner can connect to
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(try_from = "String", into = "String")]
+pub struct PoolUrl {
+    pub scheme: PoolScheme,
+    /// IPv6 hosts are kept without their brackets
+    pub host: String,
+    pub port: Option<u16>,
+    /// Anything after the host and port, Stratum V2 puts the pool's authority key here
+    pub path: Option<String>,
+}
+
+impl PoolUrl {
+    /// Refuse URLs a miner can't connect to, a port is required and only Stratum V2 takes a path
+    pub fn validate(&self) -> Result<(), Error> {
+        let invalid = |reason: &str| Error::InvalidArgument(format!("Pool URL {}: {}", self, reason));
+        if self.port.is_none() {
+            return Err(invalid("missing port"));
+        }
+        if self.path.is_some() && self.scheme != PoolScheme::Stratum2Tcp {
+            return Err(invalid("only stratum2+tcp URLs take a path"));
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for PoolUrl {
+    type Err = Error;
+
+    fn from_str(url: &str) -> Result<Self, Error> {
+        let invalid = |reason: &str| Error::InvalidArgument(format!("Pool URL {}: {}", url, reason));
+        let url = url.trim();
+        let (scheme, rest) = match url.split_once("://") {
+            Some((scheme, rest)) => (match scheme.to_lowercase().as_str() {
+                "stratum+tcp" => PoolScheme::StratumTcp,
+                "stratum+ssl" => PoolScheme::StratumSsl,
+                "stratum2+tcp" => PoolScheme::Stratum2Tcp,
+                _ => return Err(invalid("unsupported scheme")),
+            }, rest),
+            None => (PoolScheme::StratumTcp, url),
+        };
+        let (authority, path) = match rest.split_once('/') {
+            Some((authority, path)) => {
+                let path = path.trim_end_matches('/');
+                (authority, if path.is_empty() { None } else { Some(path.to_string()) })
+            },
+            None => (rest, None),
+        };
+        let (host, port) = match authority.strip_prefix('[') {
+            // Bracketed IPv6, [::1]:3333
+            Some(bracketed) => {
+                let (host, port) = bracketed.split_once(']').ok_or_else(|| invalid("invalid host"))?;
+                match port {
+                    "" => (host, None),
+                    port => (host, Some(port.strip_prefix(':').ok_or_else(|| invalid("invalid port"))?)),
+                }
+            },
+            None => match authority.rsplit_once(':') {
+                Some((host, port)) => (host, Some(port)),
+                None => (authority, None),
+            },
+        };
+        if host.is_empty() || host.contains(|c: char| c.is_whitespace() || c == '[' || c == ']') {
+            return Err(invalid("invalid host"));
+        }
+        if host.contains(':') && !authority.starts_with('[') {
+            return Err(invalid("IPv6 hosts must be in brackets"));
+        }
+        let port = match port {
+            Some(port) => match port.parse::<u16>() {
+                Ok(port) if port > 0 => Some(port),
+                _ => return Err(invalid("invalid port")),
+            },
+            None => None,
+        };
+        Ok(PoolUrl { scheme, host: host.to_string(), port, path })
+    }
+}
+
+impl TryFrom<String> for PoolUrl {
+    type Error = Error;
+
+    fn try_from(url: String) -> Result<Self, Error> {
+        url.parse()
+    }
+}
+
+impl From<PoolUrl> for String {
+    fn from(url: PoolUrl) -> Self {
+        url.to_string()
+    }
+}
+
+impl fmt::Display for PoolUrl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.host.contains(':') {
+            write!(f, "{}://[{}]", self.scheme.as_str(), self.host)?;
+        } else {
+            write!(f, "{}://{}", self.scheme.as_str(), self.host)?;
+        }
+        if let Some(port) = self.port {
+            write!(f, ":{}", port)?;
+        }
+        if let Some(path) = &self.path {
+            write!(f, "/{}", path)?;
+        }
+        Ok(())
+    }
+}
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Pool {
-    pub url: String,
+    pub url: PoolUrl,
     #[serde(rename = "user")]
     pub username: String,
     #[serde(rename = "pass")]
     pub password: Option<String>,
 }
 
-impl Default for Pool {
-    fn default() -> Self {
-        Self {
-            url: String::new(),
-            username: String::new(),
-            password: None,
+impl Pool {
+    pub fn new(url: &str, username: &str, password: Option<&str>) -> Result<Self, Error> {
+        Ok(Pool {
+            url: url.parse()?,
+            username: username.to_string(),
+            password: password.map(|p| p.to_string()),
+        })
+    }
+
+    /// Build a pool from one of a vendor's pool slots, None if the slot is unused
+    /// A URL we can't parse is skipped with a warning, so one odd pool doesn't hide the rest
+    pub fn from_slot(url: &str, username: &str, password: Option<&str>) -> Option<Self> {
+        if url.trim().is_empty() {
+            return None;
         }
+        match Pool::new(url, username, password) {
+            Ok(pool) => Some(pool),
+            Err(e) => {
+                warn!("Skipping configured pool: {}", e);
+                None
+            },
+        }
+    }
+}
+
+/// Refuse pool lists the backend can't take, before anything is sent to the miner
+pub fn validate_pools(pools: &[Pool], max: Option<usize>) -> Result<(), Error> {
+    if pools.is_empty() {
+        return Err(Error::InvalidArgument("At least one pool is required".into()));
+    }
+    for pool in pools {
+        pool.url.validate()?;
+    }
+    match max {
+        Some(max) if pools.len() > max => Err(Error::InvalidArgument(format!("{} pools given, this miner takes at most {}", pools.len(), max))),
+        _ => Ok(()),
     }
 }
 
+/// A pool slot as written to vendors with a fixed number of slots, blank past the end of the list
+pub struct PoolSlot {
+    pub url: String,
+    pub username: String,
+    pub password: String,
+}
+
+pub fn pool_slots(pools: &[Pool], slots: usize) -> Vec<PoolSlot> {
+    (0..slots).map(|i| match pools.get(i) {
+        Some(pool) => PoolSlot {
+            url: pool.url.to_string(),
+            username: pool.username.clone(),
+            password: pool.password.clone().unwrap_or_default(),
+        },
+        None => PoolSlot { url: String::new(), username: String::new(), password: String::new() },
+    }).collect()
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum PoolStatus {
+    Alive,
+    Dead,
+    Disabled,
+}
+
+impl PoolStatus {
+    /// cgminer's status string, anything other than Alive or Disabled isn't taking our shares
+    pub(crate) fn from_cgminer(status: &str) -> Self {
+        match status.to_lowercase().as_str() {
+            "alive" => PoolStatus::Alive,
+            "disabled" => PoolStatus::Disabled,
+            _ => PoolStatus::Dead,
+        }
+    }
+}
+
+/// Live statistics for one configured pool
+#[derive(Debug, Serialize, Clone)]
+pub struct PoolStats {
+    pub url: String,
+    pub username: String,
+    pub status: PoolStatus,
+    /// The pool currently receiving work, false on every pool means the miner isn't hashing
+    pub active: bool,
+    /// Lower is preferred, 0 is the primary pool
+    pub priority: usize,
+    pub accepted: usize,
+    pub rejected: usize,
+    pub stale: usize,
+    pub difficulty_accepted: f64,
+    /// In UTC, unlike log and fault timestamps which are in the miner's local time
+    pub last_share_time: Option<DateTime<Utc>>,
+    pub stratum_difficulty: Option<f64>,
+}
+
+/// Power supply readings, fields the PSU doesn't report are None
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct PsuInfo {
+    /// Volts
+    pub input_voltage: Option<f64>,
+    /// Volts
+    pub output_voltage: Option<f64>,
+    /// Amps
+    pub current: Option<f64>,
+    pub power: Option<Power>,
+    /// RPM
+    pub fan_speed: Option<u32>,
+}
+
+/// Air temperatures around the miner
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct Environment {
+    pub inlet_temp: Option<Temperature>,
+    pub outlet_temp: Option<Temperature>,
+}
+
+/// Share and hardware error counters since the miner started hashing
+/// Fields a vendor doesn't report are None
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct ShareStats {
+    pub accepted: usize,
+    pub rejected: usize,
+    pub stale: Option<usize>,
+    pub discarded: Option<usize>,
+    pub hardware_errors: Option<usize>,
+    pub best_share: Option<usize>,
+    pub difficulty_accepted: Option<f64>,
+    pub difficulty_rejected: Option<f64>,
+    /// Percent of submitted difficulty the pool rejected
+    pub pool_rejected_percent: Option<f64>,
+    pub pool_stale_percent: Option<f64>,
+    /// Percent of work the ASICs got wrong
+    pub hardware_error_percent: Option<f64>,
+}
+
+/// cgminer reports 0 for a pool that hasn't submitted a share yet
+pub(crate) fn share_time_from(secs: usize) -> Option<DateTime<Utc>> {
+    if secs == 0 {
+        return None;
+    }
+    DateTime::from_timestamp(secs as i64, 0)
+}
+
+/// Everything a fleet poller typically wants from a miner, gathered in one call
+/// Backends fetch each underlying response at most once
+#[derive(Debug, Serialize, Clone)]
+pub struct MinerSnapshot {
+    pub model: String,
+    pub mac: String,
+    pub hashrate: Hashrate,
+    /// None if the miner can't measure its power and there's no rated efficiency to estimate it from
+    pub power: Option<Power>,
+    /// True if power was estimated from the hashrate rather than read from the miner
+    pub power_estimated: bool,
+    /// None if the power is unknown or the miner isn't hashing
+    pub efficiency: Option<Efficiency>,
+    pub nameplate_rate: Hashrate,
+    pub temperature: Temperature,
+    pub fan_speed: Vec<u32>,
+    /// None if the miner does not support reading pools
+    pub pools: Option<Vec<Pool>>,
+    /// None if the miner does not support reading its sleep state
+    pub sleep: Option<bool>,
+}
+
+/// Vendor neutral view of a single hashboard
+#[derive(Debug, Serialize, Clone)]
+pub struct Hashboard {
+    pub index: usize,
+    pub online: bool,
+    pub hashrate: Option<Hashrate>,
+    pub ideal_rate: Option<Hashrate>,
+    pub chip_count: Option<usize>,
+    /// Hottest chip sensor on the board
+    pub chip_temp: Option<Temperature>,
+    /// Hottest PCB sensor on the board
+    pub pcb_temp: Option<Temperature>,
+    /// Average chip frequency in MHz
+    pub frequency: Option<f64>,
+    pub hw_errors: Option<usize>,
+    pub serial: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FaultCategory {
+    Fan,
+    Power,
+    Temperature,
+    Hashboard,
+    Chip,
+    Eeprom,
+    ControlBoard,
+    Firmware,
+    Network,
+    Pool,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum FaultSeverity {
+    /// The miner is still hashing but something needs attention
+    Warning,
+    /// Part of the miner is not hashing
+    Error,
+    /// The miner is not hashing or is at risk of damage
+    Critical,
+}
+
+/// A fault reported by a miner
+#[derive(Debug, Serialize, Clone, PartialEq, Eq, Hash)]
+pub struct MinerFault {
+    pub category: FaultCategory,
+    pub severity: FaultSeverity,
+    /// Index of the affected fan, hashboard, chain or pool if known
+    pub component: Option<usize>,
+    /// Vendor specific error code if the miner reports one
+    pub code: Option<String>,
+    pub message: String,
+    /// When the miner first reported this fault, in the miner's local time
+    pub first_seen: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Warning,
+    Error,
+    Critical,
+}
+
+/// A single parsed log line
+#[derive(Debug, Serialize, Clone)]
+pub struct LogEntry {
+    /// In the miner's local time
+    pub timestamp: Option<NaiveDateTime>,
+    /// Taken from the log when present, otherwise guessed from the message
+    pub level: Option<LogLevel>,
+    /// Process or source file that wrote the line
+    pub source: Option<String>,
+    pub message: String,
+}
+
+/// Vendor neutral performance profile
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WorkMode {
+    Sleep,
+    LowPower,
+    Normal,
+    HighPerformance,
+    /// A vendor specific level with no neutral equivalent, passed through as the vendor numbers it
+    Vendor(u8),
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FanConfig {
+    /// The miner controls fan speed from its temperature sensors
+    Auto,
+    /// Fixed PWM duty cycle in percent
+    Fixed(u8),
+    /// Fans off and fan faults ignored, for immersion or hydro cooling
+    Immersion,
+}
+
+impl FanConfig {
+    /// Errors if a fixed duty cycle is over 100%
+    pub fn validate(&self) -> Result<(), Error> {
+        match self {
+            FanConfig::Fixed(pct) if *pct > 100 => Err(Error::InvalidArgument(format!("Fan PWM {}% is over 100%", pct))),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Frequency and voltage targets, fields left as None are not changed
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+pub struct TuningConfig {
+    /// Chip frequency in MHz
+    pub frequency: Option<f64>,
+    /// Chip frequency as a percent offset from stock, for firmware that only takes relative changes
+    pub frequency_offset: Option<i8>,
+    /// Board voltage in volts
+    pub voltage: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct BoardTuning {
+    pub index: usize,
+    /// None if the firmware only has a global setting
+    pub target: Option<TuningConfig>,
+    /// Average chip frequency the board is running at in MHz, to check a change took effect
+    pub frequency: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct Tuning {
+    pub global: TuningConfig,
+    pub boards: Vec<BoardTuning>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct NetworkConfig {
+    pub dhcp: bool,
+    /// The address fields are required when dhcp is off, and ignored by set_network_config when on
+    pub ip: Option<Ipv4Addr>,
+    pub netmask: Option<Ipv4Addr>,
+    pub gateway: Option<Ipv4Addr>,
+    pub dns: Vec<Ipv4Addr>,
+    /// None leaves the hostname as is
+    pub hostname: Option<String>,
+}
+
+impl NetworkConfig {
+    /// Errors if a static config is missing its address, netmask or gateway
+    pub fn validate(&self) -> Result<(), Error> {
+        if !self.dhcp && (self.ip.is_none() || self.netmask.is_none() || self.gateway.is_none()) {
+            return Err(Error::InvalidArgument("Static network config needs an ip, netmask and gateway".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Miners report empty strings for unset addresses
+    pub(crate) fn addr_from(s: &str) -> Option<Ipv4Addr> {
+        s.trim().parse().ok()
+    }
+
+    /// DNS servers come space or comma separated depending on the firmware
+    pub(crate) fn dns_from(s: &str) -> Vec<Ipv4Addr> {
+        s.split(|c: char| c == ',' || c.is_whitespace())
+            .filter_map(Self::addr_from)
+            .collect()
+    }
+
+    pub(crate) fn dns_to(&self, sep: &str) -> String {
+        self.dns.iter().map(|d| d.to_string()).collect::<Vec<_>>().join(sep)
+    }
+}
+
+/// How long a miner gets to take an image, flash it and come back
+pub(crate) const UPGRADE_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Reported in order by upgrade_firmware, stages a backend can't observe are skipped
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum UpgradeStage {
+    Uploading,
+    Flashing,
+    Rebooting,
+    /// The miner is answering on its API again
+    Online,
+}
+
+/// A configuration backup, restored with import_config
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MinerConfig {
+    /// get_type of the miner this came from, import_config refuses a config from another vendor
+    pub vendor: String,
+    pub pools: Option<Vec<Pool>>,
+    pub work_mode: Option<WorkMode>,
+    pub fan: Option<FanConfig>,
+    pub tuning: Option<TuningConfig>,
+    pub network: Option<NetworkConfig>,
+    /// Settings with no neutral equivalent, such as Antminer's api-allow
+    /// Backends that export these restore them first and apply the neutral fields on top,
+    /// so a neutral field that is set always wins, backends that don't export them ignore them
+    pub vendor_settings: Option<serde_json::Value>,
+}
+
+/// Read everything the miner's capabilities allow, backends add their vendor settings on top
+pub async fn export_common<M: Miner + Sync + ?Sized>(miner: &M) -> Result<MinerConfig, Error> {
+    let caps = miner.capabilities();
+    let has = |c: Capability| caps.contains(&c);
+    Ok(MinerConfig {
+        vendor: miner.get_type().to_string(),
+        pools: if has(Capability::ReadPools) { Some(miner.get_pools().await?) } else { None },
+        work_mode: if has(Capability::ReadWorkMode) { Some(miner.get_work_mode().await?) } else { None },
+        fan: if has(Capability::ReadFanConfig) { Some(miner.get_fan_config().await?) } else { None },
+        tuning: if has(Capability::ReadTuning) { Some(miner.get_tuning().await?.global) } else { None },
+        network: if has(Capability::ReadNetwork) { Some(miner.get_network_config().await?) } else { None },
+        vendor_settings: None,
+    })
+}
+
+/// Skip settings the miner can't take rather than failing the whole restore
+pub(crate) fn skip_unsupported(setting: &str, res: Result<(), Error>) -> Result<(), Error> {
+    match res {
+        Err(Error::NotSupported) => {
+            warn!("Skipping {}, not supported by this miner", setting);
+            Ok(())
+        },
+        res => res,
+    }
+}
+
+/// Restore the neutral fields of a config, network last as the miner moves once it's applied
+pub async fn import_common<M: Miner + Send + ?Sized>(miner: &mut M, config: MinerConfig) -> Result<(), Error> {
+    if config.vendor != miner.get_type() {
+        return Err(Error::InvalidArgument(format!("Config is for {}, not {}", config.vendor, miner.get_type())));
+    }
+    if let Some(pools) = config.pools {
+        skip_unsupported("pools", miner.set_pools(pools).await)?;
+    }
+    if let Some(mode) = config.work_mode {
+        skip_unsupported("work mode", miner.set_work_mode(mode).await)?;
+    }
+    if let Some(fan) = config.fan {
+        skip_unsupported("fan config", miner.set_fan_config(fan).await)?;
+    }
+    if let Some(tuning) = config.tuning {
+        skip_unsupported("tuning", miner.set_tuning(None, tuning).await)?;
+    }
+    if let Some(network) = config.network {
+        skip_unsupported("network config", miner.set_network_config(network).await)?;
+    }
+    Ok(())
+}
+
+/// factory_reset takes the miner's MAC as confirmation so a stale IP can't wipe the wrong machine
+pub fn check_mac_confirmation(mac: &str, confirm: &str) -> Result<(), Error> {
+    let normalize = |s: &str| s.trim().to_lowercase().replace('-', ":");
+    if normalize(mac) == normalize(confirm) {
+        Ok(())
+    } else {
+        Err(Error::InvalidArgument(format!("Confirmation {} does not match the miner's MAC {}", confirm, mac)))
+    }
+}
+
+/// Features a backend supports, so callers can plan without catching Error::NotSupported
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Capability {
+    ReadPools,
+    WritePools,
+    PoolStats,
+    ShareStats,
+    FirmwareVersion,
+    SerialNumber,
+    Uptime,
+    PsuInfo,
+    Environment,
+    ReadSleep,
+    WriteSleep,
+    ReadBlink,
+    WriteBlink,
+    /// Both get_logs and get_log_entries
+    Logs,
+    Errors,
+    /// get_power is read from the miner
+    MeasuredPower,
+    /// get_power is estimated from the hashrate and the model's rated efficiency
+    EstimatedPower,
+    /// get_hashboards returns per-board data
+    Hashboards,
+    ReadPowerLimit,
+    WritePowerLimit,
+    ReadWorkMode,
+    WriteWorkMode,
+    ReadFanConfig,
+    WriteFanConfig,
+    ReadTuning,
+    WriteTuning,
+    /// set_tuning accepts a single hashboard
+    BoardTuning,
+    ReadNetwork,
+    WriteNetwork,
+    SetPassword,
+    UpgradeFirmware,
+    FactoryReset,
+}
+
 #[derive(Debug)]
 pub struct MinerError {
     pub re: &'static Lazy<Regex>,
     pub msg: &'static str,
+    pub category: FaultCategory,
+    pub severity: FaultSeverity,
 }
 
 impl MinerError {
@@ -44,8 +637,40 @@ impl MinerError {
             None
         }
     }
+
+    /// Search the given log for this error
+    /// The first capture, if numeric, is taken as the affected component
+    pub fn get_fault(&self, log: &str) -> Option<MinerFault> {
+        let caps = self.re.captures(log)?;
+        let component = match self.category {
+            FaultCategory::Firmware => None,
+            _ => caps.iter().skip(1).flatten().next().and_then(|c| c.as_str().parse().ok()),
+        };
+        Some(MinerFault {
+            category: self.category,
+            severity: self.severity,
+            component,
+            code: None,
+            message: self.get_msg(log)?,
+            first_seen: None,
+        })
+    }
+
+    /// Match a single vendor error code, the whole code must match
+    pub fn get_code_fault(&self, code: &str, first_seen: Option<NaiveDateTime>) -> Option<MinerFault> {
+        let whole = self.re.find(code)?;
+        if whole.start() != 0 || whole.end() != code.len() {
+            return None;
+        }
+        let mut fault = self.get_fault(code)?;
+        fault.code = Some(code.to_string());
+        fault.first_seen = first_seen;
+        Some(fault)
+    }
 }
 
+/// Only the core reads and writes are required, optional features default to Error::NotSupported
+/// and export_config, import_config and get_snapshot are built from the rest
 #[async_trait]
 pub trait Miner {
     fn new(client: Client, ip: String, port: u16) -> Self
@@ -53,41 +678,190 @@ pub trait Miner {
 
     fn get_type(&self) -> &'static str;
 
+    /// Features this backend supports, anything not listed returns Error::NotSupported
+    fn capabilities(&self) -> &'static [Capability] {
+        &[]
+    }
+
+    /// Most pools set_pools takes, None if there's no fixed limit
+    fn max_pools(&self) -> Option<usize> {
+        None
+    }
+
     async fn get_model(&self) -> Result<String, Error>;
 
     async fn auth(&mut self, username: &str, password: &str) -> Result<(), Error>;
 
+    /// Change the password used by auth, the miner stays authenticated with the new password
+    async fn set_password(&mut self, _old: &str, _new: &str) -> Result<(), Error> {
+        Err(Error::NotSupported)
+    }
+
     async fn reboot(&mut self) -> Result<(), Error>;
 
-    async fn get_hashrate(&self) -> Result<f64, Error>;
+    /// Restore factory settings, confirm_mac must match get_mac or nothing is done
+    /// Network settings and passwords are reset too, so expect the miner to move and need auth again
+    async fn factory_reset(&mut self, _confirm_mac: &str) -> Result<(), Error> {
+        Err(Error::NotSupported)
+    }
+
+    async fn get_hashrate(&self) -> Result<Hashrate, Error>;
+
+    async fn get_power(&self) -> Result<Power, Error>;
+
+    /// Fails with NotHashing when there's no hashrate to divide by
+    async fn get_efficiency(&self) -> Result<Efficiency, Error>;
 
-    async fn get_power(&self) -> Result<f64, Error>;
+    async fn get_nameplate_rate(&self) -> Result<Hashrate, Error>;
 
-    async fn get_efficiency(&self) -> Result<f64, Error>;
+    async fn get_temperature(&self) -> Result<Temperature, Error>;
 
-    async fn get_nameplate_rate(&self) -> Result<f64, Error>;
+    /// Power supply readings, fields the PSU doesn't report are left as None
+    async fn get_psu_info(&self) -> Result<PsuInfo, Error> {
+        Err(Error::NotSupported)
+    }
+
+    /// Air temperatures around the miner, as opposed to the chips
+    async fn get_environment(&self) -> Result<Environment, Error> {
+        Err(Error::NotSupported)
+    }
 
-    async fn get_temperature(&self) -> Result<f64, Error>;
+    /// The power target the miner tunes itself to stay under
+    async fn get_power_limit(&self) -> Result<Power, Error> {
+        Err(Error::NotSupported)
+    }
+
+    async fn set_power_limit(&mut self, _limit: Power) -> Result<(), Error> {
+        Err(Error::NotSupported)
+    }
 
     async fn get_fan_speed(&self) -> Result<Vec<u32>, Error>;
 
+    async fn get_fan_config(&self) -> Result<FanConfig, Error> {
+        Err(Error::NotSupported)
+    }
+
+    async fn set_fan_config(&mut self, _config: FanConfig) -> Result<(), Error> {
+        Err(Error::NotSupported)
+    }
+
     async fn get_pools(&self) -> Result<Vec<Pool>, Error>;
 
     async fn set_pools(&mut self, pools: Vec<Pool>) -> Result<(), Error>;
 
+    /// Live statistics for each configured pool, in priority order
+    async fn get_pool_stats(&self) -> Result<Vec<PoolStats>, Error> {
+        Err(Error::NotSupported)
+    }
+
+    /// Share totals across all pools, the rejection rate is the first sign of pool or network trouble
+    async fn get_share_stats(&self) -> Result<ShareStats, Error> {
+        Err(Error::NotSupported)
+    }
+
     async fn get_sleep(&self) -> Result<bool, Error>;
 
     async fn set_sleep(&mut self, sleep: bool) -> Result<(), Error>;
 
+    async fn get_work_mode(&self) -> Result<WorkMode, Error> {
+        Err(Error::NotSupported)
+    }
+
+    /// Modes the miner has no equivalent for return Error::NotSupported
+    async fn set_work_mode(&mut self, _mode: WorkMode) -> Result<(), Error> {
+        Err(Error::NotSupported)
+    }
+
     async fn get_blink(&self) -> Result<bool, Error>;
 
     async fn set_blink(&mut self, blink: bool) -> Result<(), Error>;
 
     async fn get_logs(&mut self) -> Result<Vec<String>, Error>;
 
+    /// Same as get_logs but parsed into timestamped entries
+    async fn get_log_entries(&mut self) -> Result<Vec<LogEntry>, Error> {
+        Err(Error::NotSupported)
+    }
+
     async fn get_mac(&self) -> Result<String, Error>;
 
-    async fn get_errors(&mut self) -> Result<Vec<String>, Error>;
+    async fn get_firmware_version(&self) -> Result<String, Error> {
+        Err(Error::NotSupported)
+    }
+
+    /// The unit's serial, backends without one report the first hashboard's
+    async fn get_serial_number(&self) -> Result<String, Error> {
+        Err(Error::NotSupported)
+    }
+
+    /// Time since the mining process started
+    async fn get_uptime(&self) -> Result<Duration, Error> {
+        Err(Error::NotSupported)
+    }
+
+    async fn get_network_config(&self) -> Result<NetworkConfig, Error> {
+        Err(Error::NotSupported)
+    }
+
+    /// The miner drops off its current address once this succeeds
+    /// Of the built in backends only Antminer and Whatsminer can be changed, check for Capability::WriteNetwork
+    /// Minera only reports the OS's ifconfig and Minerva's API only reads systemInfo/network,
+    /// neither has a way to write it, so both return Error::NotSupported
+    async fn set_network_config(&mut self, _config: NetworkConfig) -> Result<(), Error> {
+        Err(Error::NotSupported)
+    }
+
+    async fn get_errors(&mut self) -> Result<Vec<MinerFault>, Error>;
+
+    /// Gather model, MAC, hashrate, power, temperatures, fans, pools and sleep state
+    /// using as few requests as the miner allows
+    async fn get_snapshot(&self) -> Result<MinerSnapshot, Error> {
+        Ok(MinerSnapshot {
+            model: self.get_model().await?,
+            mac: self.get_mac().await?,
+            hashrate: self.get_hashrate().await?,
+            power: self.get_power().await.ok(),
+            power_estimated: self.capabilities().contains(&Capability::EstimatedPower),
+            efficiency: self.get_efficiency().await.ok(),
+            nameplate_rate: self.get_nameplate_rate().await?,
+            temperature: self.get_temperature().await?,
+            fan_speed: self.get_fan_speed().await?,
+            pools: self.get_pools().await.ok(),
+            sleep: self.get_sleep().await.ok(),
+        })
+    }
+
+    /// Per hashboard details, fields the miner doesn't report are left as None
+    async fn get_hashboards(&self) -> Result<Vec<Hashboard>, Error> {
+        Err(Error::NotSupported)
+    }
+
+    /// Back up everything the miner lets us read
+    async fn export_config(&self) -> Result<MinerConfig, Error> {
+        export_common(self).await
+    }
+
+    /// Restore a backup from export_config, settings the miner can't take are skipped
+    /// Neutral fields take precedence over vendor_settings, see MinerConfig
+    async fn import_config(&mut self, config: MinerConfig) -> Result<(), Error> {
+        import_common(self, config).await
+    }
+
+    /// Upload and flash a firmware image, returns once the miner is back online
+    /// The image must be in the vendor's upgrade format, it is not checked
+    async fn upgrade_firmware(&mut self, _image: &[u8], _progress: &(dyn Fn(UpgradeStage) + Send + Sync)) -> Result<(), Error> {
+        Err(Error::NotSupported)
+    }
+
+    async fn get_tuning(&self) -> Result<Tuning, Error> {
+        Err(Error::NotSupported)
+    }
+
+    /// Tune a single hashboard, or every board if board is None
+    /// Fields the firmware can't apply return Error::NotSupported without changing anything
+    async fn set_tuning(&mut self, _board: Option<usize>, _tuning: TuningConfig) -> Result<(), Error> {
+        Err(Error::NotSupported)
+    }
 }
 
 pub struct LockMiner {
@@ -115,6 +889,14 @@ impl Miner for LockMiner {
         self.miner.get_type()
     }
 
+    fn capabilities(&self) -> &'static [Capability] {
+        self.miner.capabilities()
+    }
+
+    fn max_pools(&self) -> Option<usize> {
+        self.miner.max_pools()
+    }
+
     async fn get_model(&self) -> Result<String, Error> {
         self.miner.get_model().await
     }
@@ -123,34 +905,66 @@ impl Miner for LockMiner {
         self.miner.auth(username, password).await
     }
 
+    async fn set_password(&mut self, old: &str, new: &str) -> Result<(), Error> {
+        self.miner.set_password(old, new).await
+    }
+
     async fn reboot(&mut self) -> Result<(), Error> {
         self.miner.reboot().await
     }
 
-    async fn get_hashrate(&self) -> Result<f64, Error> {
+    async fn factory_reset(&mut self, confirm_mac: &str) -> Result<(), Error> {
+        self.miner.factory_reset(confirm_mac).await
+    }
+
+    async fn get_hashrate(&self) -> Result<Hashrate, Error> {
         self.miner.get_hashrate().await
     }
 
-    async fn get_power(&self) -> Result<f64, Error> {
+    async fn get_power(&self) -> Result<Power, Error> {
         self.miner.get_power().await
     }
 
-    async fn get_efficiency(&self) -> Result<f64, Error> {
+    async fn get_efficiency(&self) -> Result<Efficiency, Error> {
         self.miner.get_efficiency().await
     }
 
-    async fn get_nameplate_rate(&self) -> Result<f64, Error> {
+    async fn get_nameplate_rate(&self) -> Result<Hashrate, Error> {
         self.miner.get_nameplate_rate().await
     }
 
-    async fn get_temperature(&self) -> Result<f64, Error> {
+    async fn get_temperature(&self) -> Result<Temperature, Error> {
         self.miner.get_temperature().await
     }
 
+    async fn get_psu_info(&self) -> Result<PsuInfo, Error> {
+        self.miner.get_psu_info().await
+    }
+
+    async fn get_environment(&self) -> Result<Environment, Error> {
+        self.miner.get_environment().await
+    }
+
+    async fn get_power_limit(&self) -> Result<Power, Error> {
+        self.miner.get_power_limit().await
+    }
+
+    async fn set_power_limit(&mut self, limit: Power) -> Result<(), Error> {
+        self.miner.set_power_limit(limit).await
+    }
+
     async fn get_fan_speed(&self) -> Result<Vec<u32>, Error> {
         self.miner.get_fan_speed().await
     }
 
+    async fn get_fan_config(&self) -> Result<FanConfig, Error> {
+        self.miner.get_fan_config().await
+    }
+
+    async fn set_fan_config(&mut self, config: FanConfig) -> Result<(), Error> {
+        self.miner.set_fan_config(config).await
+    }
+
     async fn get_pools(&self) -> Result<Vec<Pool>, Error> {
         self.miner.get_pools().await
     }
@@ -159,6 +973,14 @@ impl Miner for LockMiner {
         self.miner.set_pools(pools).await
     }
 
+    async fn get_pool_stats(&self) -> Result<Vec<PoolStats>, Error> {
+        self.miner.get_pool_stats().await
+    }
+
+    async fn get_share_stats(&self) -> Result<ShareStats, Error> {
+        self.miner.get_share_stats().await
+    }
+
     async fn get_sleep(&self) -> Result<bool, Error> {
         self.miner.get_sleep().await
     }
@@ -167,6 +989,14 @@ impl Miner for LockMiner {
         self.miner.set_sleep(sleep).await
     }
 
+    async fn get_work_mode(&self) -> Result<WorkMode, Error> {
+        self.miner.get_work_mode().await
+    }
+
+    async fn set_work_mode(&mut self, mode: WorkMode) -> Result<(), Error> {
+        self.miner.set_work_mode(mode).await
+    }
+
     async fn get_blink(&self) -> Result<bool, Error> {
         self.miner.get_blink().await
     }
@@ -179,11 +1009,184 @@ impl Miner for LockMiner {
         self.miner.get_logs().await
     }
 
+    async fn get_log_entries(&mut self) -> Result<Vec<LogEntry>, Error> {
+        self.miner.get_log_entries().await
+    }
+
     async fn get_mac(&self) -> Result<String, Error> {
         self.miner.get_mac().await
     }
 
-    async fn get_errors(&mut self) -> Result<Vec<String>, Error> {
+    async fn get_firmware_version(&self) -> Result<String, Error> {
+        self.miner.get_firmware_version().await
+    }
+
+    async fn get_serial_number(&self) -> Result<String, Error> {
+        self.miner.get_serial_number().await
+    }
+
+    async fn get_uptime(&self) -> Result<Duration, Error> {
+        self.miner.get_uptime().await
+    }
+
+    async fn get_network_config(&self) -> Result<NetworkConfig, Error> {
+        self.miner.get_network_config().await
+    }
+
+    async fn set_network_config(&mut self, config: NetworkConfig) -> Result<(), Error> {
+        self.miner.set_network_config(config).await
+    }
+
+    async fn get_errors(&mut self) -> Result<Vec<MinerFault>, Error> {
         self.miner.get_errors().await
     }
+
+    async fn get_snapshot(&self) -> Result<MinerSnapshot, Error> {
+        self.miner.get_snapshot().await
+    }
+
+    async fn get_hashboards(&self) -> Result<Vec<Hashboard>, Error> {
+        self.miner.get_hashboards().await
+    }
+
+    async fn export_config(&self) -> Result<MinerConfig, Error> {
+        self.miner.export_config().await
+    }
+
+    async fn import_config(&mut self, config: MinerConfig) -> Result<(), Error> {
+        self.miner.import_config(config).await
+    }
+
+    async fn upgrade_firmware(&mut self, image: &[u8], progress: &(dyn Fn(UpgradeStage) + Send + Sync)) -> Result<(), Error> {
+        self.miner.upgrade_firmware(image, progress).await
+    }
+
+    async fn get_tuning(&self) -> Result<Tuning, Error> {
+        self.miner.get_tuning().await
+    }
+
+    async fn set_tuning(&mut self, board: Option<usize>, tuning: TuningConfig) -> Result<(), Error> {
+        self.miner.set_tuning(board, tuning).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ClientBuilder;
+
+    fn backends() -> Vec<Box<dyn Miner + Send + Sync>> {
+        let client = ClientBuilder::new().build().unwrap();
+        let ip = || "10.0.0.20".to_string();
+        vec![
+            #[cfg(feature = "antminer")]
+            Box::new(crate::miners::antminer::Antminer::new(client.clone(), ip(), 4028)),
+            #[cfg(feature = "avalon")]
+            Box::new(crate::miners::avalon::Avalon::new(client.clone(), ip(), 4028)),
+            #[cfg(feature = "minerva")]
+            Box::new(crate::miners::minerva::Minerva::new(client.clone(), ip(), 4028)),
+            #[cfg(feature = "minerva")]
+            Box::new(crate::miners::minerva::Minera::new(client.clone(), ip(), 4028)),
+            #[cfg(feature = "whatsminer")]
+            Box::new(crate::miners::whatsminer::Whatsminer::new(client.clone(), ip(), 4028)),
+        ]
+    }
+
+    #[test]
+    fn it_lists_consistent_capabilities() {
+        for miner in backends() {
+            let caps = miner.capabilities();
+            let has = |c: Capability| caps.contains(&c);
+            for (i, cap) in caps.iter().enumerate() {
+                assert!(!caps[i + 1..].contains(cap), "{} lists {:?} twice", miner.get_type(), cap);
+            }
+            for (write, read) in [
+                (Capability::WritePools, Capability::ReadPools),
+                (Capability::WriteSleep, Capability::ReadSleep),
+                (Capability::WriteBlink, Capability::ReadBlink),
+                (Capability::WritePowerLimit, Capability::ReadPowerLimit),
+                (Capability::WriteWorkMode, Capability::ReadWorkMode),
+                (Capability::WriteTuning, Capability::ReadTuning),
+                (Capability::BoardTuning, Capability::WriteTuning),
+                (Capability::WriteNetwork, Capability::ReadNetwork),
+            ] {
+                assert!(!has(write) || has(read), "{} has {:?} without {:?}", miner.get_type(), write, read);
+            }
+            assert!(!(has(Capability::MeasuredPower) && has(Capability::EstimatedPower)), "{} measures and estimates power", miner.get_type());
+        }
+    }
+
+    #[test]
+    fn it_confirms_macs() {
+        assert!(check_mac_confirmation("C6:07:20:00:1A:2B", "c6:07:20:00:1a:2b").is_ok());
+        assert!(check_mac_confirmation("c6:07:20:00:1a:2b", " C6-07-20-00-1A-2B ").is_ok());
+        assert!(matches!(check_mac_confirmation("c6:07:20:00:1a:2b", "c6:07:20:00:1a:2c"), Err(Error::InvalidArgument(_))));
+        assert!(check_mac_confirmation("c6:07:20:00:1a:2b", "").is_err());
+    }
+
+    #[test]
+    fn it_validates_network_configs() {
+        let dhcp: NetworkConfig = serde_json::from_str(r#"{"dhcp":true,"ip":null,"netmask":null,"gateway":null,"dns":[],"hostname":null}"#).unwrap();
+        assert!(dhcp.validate().is_ok());
+        let fixed: NetworkConfig = serde_json::from_str(r#"{"dhcp":false,"ip":"10.0.0.20","netmask":"255.255.255.0","gateway":"10.0.0.1","dns":["1.1.1.1"],"hostname":"s19-rack4"}"#).unwrap();
+        assert!(fixed.validate().is_ok());
+        let no_gateway = NetworkConfig { gateway: None, ..fixed.clone() };
+        assert!(matches!(no_gateway.validate(), Err(Error::InvalidArgument(_))));
+        assert!(NetworkConfig { ip: None, ..fixed.clone() }.validate().is_err());
+        assert_eq!(NetworkConfig::dns_from("8.8.8.8, 1.1.1.1 bogus"), vec![Ipv4Addr::new(8, 8, 8, 8), Ipv4Addr::new(1, 1, 1, 1)]);
+        assert_eq!(NetworkConfig::addr_from(""), None);
+    }
+
+    #[test]
+    fn it_parses_pool_urls() {
+        let url: PoolUrl = "stratum+ssl://btc.pool.com:443".parse().unwrap();
+        assert_eq!(url.scheme, PoolScheme::StratumSsl);
+        assert_eq!(url.host, "btc.pool.com");
+        assert_eq!(url.port, Some(443));
+        assert_eq!("btc.pool.com:3333".parse::<PoolUrl>().unwrap().to_string(), "stratum+tcp://btc.pool.com:3333");
+        assert!("http://btc.pool.com:3333".parse::<PoolUrl>().is_err());
+        assert!("stratum+tcp://:3333".parse::<PoolUrl>().is_err());
+        assert!("stratum+tcp://btc.pool.com:port".parse::<PoolUrl>().is_err());
+        assert!(serde_json::from_str::<Pool>(r#"{"url":"stratum+tcp://btc.pool.com:99999","user":"worker","pass":null}"#).is_err());
+    }
+
+    #[test]
+    fn it_parses_sv2_and_ipv6_pool_urls() {
+        let sv2 = "stratum2+tcp://sv2.pool.com:3336/9bXiEd8boQVhq7WddEcERUL5tyyJVFYdU8th3HfbNXK3Yw6GRXh";
+        let url: PoolUrl = sv2.parse().unwrap();
+        assert_eq!(url.scheme, PoolScheme::Stratum2Tcp);
+        assert_eq!(url.port, Some(3336));
+        assert_eq!(url.path.as_deref(), Some("9bXiEd8boQVhq7WddEcERUL5tyyJVFYdU8th3HfbNXK3Yw6GRXh"));
+        assert_eq!(url.to_string(), sv2);
+        assert!(url.validate().is_ok());
+
+        let url: PoolUrl = "stratum+tcp://[::1]:3333".parse().unwrap();
+        assert_eq!(url.host, "::1");
+        assert_eq!(url.port, Some(3333));
+        assert_eq!(url.to_string(), "stratum+tcp://[::1]:3333");
+        assert!("stratum+tcp://::1:3333".parse::<PoolUrl>().is_err());
+
+        // Readable, but not something set_pools will send
+        let url: PoolUrl = "stratum+tcp://btc.pool.com".parse().unwrap();
+        assert_eq!(url.port, None);
+        assert_eq!(url.to_string(), "stratum+tcp://btc.pool.com");
+        assert!(url.validate().is_err());
+        assert!("stratum+tcp://btc.pool.com:3333/path".parse::<PoolUrl>().unwrap().validate().is_err());
+        let pool = Pool::new("stratum+tcp://btc.pool.com", "worker", None).unwrap();
+        assert!(validate_pools(&[pool], None).is_err());
+        assert!(Pool::from_slot("http://btc.pool.com:3333", "worker", None).is_none());
+        assert!(Pool::from_slot("stratum+tcp://btc.pool.com", "worker", None).is_some());
+    }
+
+    #[test]
+    fn it_validates_pool_counts() {
+        let pool = Pool::new("stratum+tcp://btc.pool.com:3333", "worker", None).unwrap();
+        assert!(validate_pools(&[], Some(3)).is_err());
+        assert!(validate_pools(&vec![pool.clone(); 4], Some(3)).is_err());
+        assert!(validate_pools(&vec![pool.clone(); 4], None).is_ok());
+        let slots = pool_slots(&[pool], 3);
+        assert_eq!(slots.len(), 3);
+        assert_eq!(slots[0].url, "stratum+tcp://btc.pool.com:3333");
+        assert!(slots[2].url.is_empty());
+    }
 }
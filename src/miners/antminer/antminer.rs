@@ -3,13 +3,16 @@ use serde_json::json;
 use std::{
     collections::HashSet,
     cell::Cell,
+    time::Duration,
 };
 use phf::phf_map;
 
-use crate::util::digest_auth::WithDigestAuth;
-use crate::miner::{Miner, Pool};
+use crate::util::digest_auth::{digest_header, WithDigestAuth};
+use crate::miner::{check_mac_confirmation, export_common, import_common, pool_slots, validate_pools, BoardTuning, Capability, FanConfig, Hashboard, LogEntry, Miner, MinerConfig, MinerFault, MinerSnapshot, NetworkConfig, Pool, PoolStats, PoolStatus, ShareStats, Tuning, TuningConfig, UpgradeStage, WorkMode, UPGRADE_TIMEOUT};
+use crate::util::{log, multipart, upload};
 use crate::miners::antminer::cgi;
 use crate::error::Error;
+use crate::units::{Efficiency, Hashrate, Power, Temperature};
 use crate::Client;
 use crate::miners::antminer::error::AntminerErrors;
 
@@ -36,6 +39,131 @@ pub struct Antminer {
     client: Client,
 }
 
+impl Antminer {
+    async fn get_system_info(&self) -> Result<cgi::SystemInfoResponse, Error> {
+        let resp = self.client.http_client
+            .get(format!("http://{}/cgi-bin/get_system_info.cgi", self.ip))
+            .send_with_digest_auth(&self.username, &self.password)
+            .await?;
+        if resp.status().is_success() {
+            Ok(resp.json().await?)
+        } else {
+            Err(Error::HttpRequestFailed)
+        }
+    }
+
+    async fn get_summary(&self) -> Result<cgi::SummaryResponse, Error> {
+        let resp = self.client.http_client
+            .get(format!("http://{}/cgi-bin/summary.cgi", self.ip))
+            .send_with_digest_auth(&self.username, &self.password)
+            .await?;
+        if resp.status().is_success() {
+            //TODO: We should parse the status and properly return errors
+            let text = resp.text().await?;
+            Ok(serde_json::from_str(&text)?)
+        } else {
+            Err(Error::HttpRequestFailed)
+        }
+    }
+
+    async fn get_stats(&self) -> Result<cgi::StatsResponse, Error> {
+        let resp = self.client.http_client
+            .get(format!("http://{}/cgi-bin/stats.cgi", self.ip))
+            .send_with_digest_auth(&self.username, &self.password)
+            .await?;
+        if resp.status().is_success() {
+            Ok(resp.json().await?)
+        } else {
+            Err(Error::HttpRequestFailed)
+        }
+    }
+
+    async fn get_pools_cgi(&self) -> Result<cgi::PoolsResponse, Error> {
+        let resp = self.client.http_client
+            .get(format!("http://{}/cgi-bin/pools.cgi", self.ip))
+            .send_with_digest_auth(&self.username, &self.password)
+            .await?;
+        if resp.status().is_success() {
+            Ok(resp.json().await?)
+        } else {
+            Err(Error::HttpRequestFailed)
+        }
+    }
+
+    async fn get_conf(&self) -> Result<cgi::GetConfResponse, Error> {
+        let resp = self.client.http_client
+            .get(format!("http://{}/cgi-bin/get_miner_conf.cgi", self.ip))
+            .send_with_digest_auth(&self.username, &self.password)
+            .await?;
+        if resp.status().is_success() {
+            Ok(resp.json().await?)
+        } else {
+            Err(Error::HttpRequestFailed)
+        }
+    }
+
+    /// Normalizes the reported miner type into the keys used by POWER_MAP
+    fn model_from(sys_info: &cgi::SystemInfoResponse) -> String {
+        sys_info.minertype.replace("Antminer ", "").replace(" ", "").to_lowercase()
+    }
+
+    /// Rates come with a rate_unit, fall back to GH/s if it is one we don't recognize
+    fn rate_from(rate: f64, unit: &str) -> Hashrate {
+        Hashrate::from_unit(rate, unit).unwrap_or(Hashrate::from_ghs(rate))
+    }
+
+    fn hashrate_from(summary: &cgi::SummaryResponse) -> Hashrate {
+        if let Some(sum) = summary.summary.get(0) {
+            Self::rate_from(sum.rate_5s, &sum.rate_unit)
+        } else {
+            // Miner can not return a summary if it is not mining
+            Hashrate::default()
+        }
+    }
+
+    fn efficiency_from(model: String) -> Result<Efficiency, Error> {
+        Ok(Efficiency::from_j_per_th(*POWER_MAP.get(model.as_str()).ok_or(Error::UnknownModel(model))?))
+    }
+
+    fn nameplate_from(stats: &cgi::StatsResponse) -> Hashrate {
+        if let Some(stat) = stats.stats.get(0) {
+            Self::rate_from(stat.rate_ideal, &stat.rate_unit)
+        } else {
+            //TODO: Decide to return an error or just an empty vector
+            Hashrate::default()
+        }
+    }
+
+    fn temperature_from(stats: &cgi::StatsResponse) -> Temperature {
+        // Antminer doesn't report a single temperature,
+        // instead return the average of the chip sensors
+        if let Some(stat) = stats.stats.get(0) {
+            let mut ret = 0.0;
+            let mut ntemp = 0;
+            for chain in &stat.chain {
+                for temp in &chain.temp_chip {
+                    ntemp += 1;
+                    ret += *temp as f64;
+                }
+            }
+            Temperature::from_celsius(ret / ntemp as f64)
+        } else {
+            //TODO: Decide to return an error or just an empty vector
+            Temperature::default()
+        }
+    }
+
+    fn fans_from(stats: &cgi::StatsResponse) -> Vec<u32> {
+        if let Some(stat) = stats.stats.get(0) {
+            //TODO: Gotta be a way to avoid this clone
+            stat.fan.clone()
+        } else {
+            //TODO: Decide to return an error or just an empty vector
+            vec![]
+        }
+    }
+}
+
 #[async_trait]
 impl Miner for Antminer {
     fn new(client: Client, ip: String, port: u16) -> Self {
@@ -52,17 +180,43 @@ impl Miner for Antminer {
         "Antminer"
     }
 
+    fn capabilities(&self) -> &'static [Capability] {
+        &[
+            Capability::ReadPools,
+            Capability::WritePools,
+            Capability::PoolStats,
+            Capability::ShareStats,
+            Capability::FirmwareVersion,
+            Capability::SerialNumber,
+            Capability::Uptime,
+            Capability::ReadSleep,
+            Capability::WriteSleep,
+            Capability::ReadBlink,
+            Capability::WriteBlink,
+            Capability::Logs,
+            Capability::Errors,
+            Capability::EstimatedPower,
+            Capability::Hashboards,
+            Capability::ReadWorkMode,
+            Capability::WriteWorkMode,
+            Capability::ReadFanConfig,
+            Capability::WriteFanConfig,
+            Capability::ReadTuning,
+            Capability::WriteTuning,
+            Capability::ReadNetwork,
+            Capability::WriteNetwork,
+            Capability::SetPassword,
+            Capability::UpgradeFirmware,
+            Capability::FactoryReset,
+        ]
+    }
+
+    fn max_pools(&self) -> Option<usize> {
+        Some(3)
+    }
+
     async fn get_model(&self) -> Result<String, Error> {
-        let resp = self.client.http_client
-            .get(&format!("http://{}/cgi-bin/get_system_info.cgi", self.ip))
-            .send_with_digest_auth(&self.username, &self.password)
-            .await?;
-        if resp.status().is_success() {
-            let sys_info: cgi::SystemInfoResponse = resp.json().await?;
-            Ok(sys_info.minertype.replace("Antminer ", "").replace(" ", "").to_lowercase())
-        } else {
-            Err(Error::HttpRequestFailed)
-        }
+        Ok(Self::model_from(&self.get_system_info().await?))
     }
 
     async fn auth(&mut self, username: &str, password: &str) -> Result<(), Error> {
@@ -70,7 +224,7 @@ impl Miner for Antminer {
         self.password = password.to_string();
         // Test authentication with a simple get request
         match self.client.http_client
-            .get(&format!("http://{}/cgi-bin/get_miner_conf.cgi", self.ip))
+            .get(format!("http://{}/cgi-bin/get_miner_conf.cgi", self.ip))
             .send_with_digest_auth(&self.username, &self.password)
             .await {
                 Ok(resp) => {
@@ -84,9 +238,27 @@ impl Miner for Antminer {
             }
     }
 
+    async fn set_password(&mut self, old: &str, new: &str) -> Result<(), Error> {
+        let resp = self.client.http_client
+            .post(format!("http://{}/cgi-bin/passwd.cgi", self.ip))
+            .json(&json!({
+                "curPwd": old,
+                "newPwd": new,
+                "confirmPwd": new,
+            }))
+            .send_with_digest_auth(&self.username, old)
+            .await?;
+        if resp.status().is_success() {
+            self.password = new.to_string();
+            Ok(())
+        } else {
+            Err(Error::Unauthorized)
+        }
+    }
+
     async fn reboot(&mut self) -> Result<(), Error> {
         let resp = self.client.http_client
-            .get(&format!("http://{}/cgi-bin/reboot.cgi", self.ip))
+            .get(format!("http://{}/cgi-bin/reboot.cgi", self.ip))
             .send_with_digest_auth(&self.username, &self.password)
             .await;
         // Miner reboots before a response is returned, so actually we want this to fail
@@ -97,140 +269,87 @@ impl Miner for Antminer {
         }
     }
 
-    async fn get_hashrate(&self) -> Result<f64, Error> {
+    async fn factory_reset(&mut self, confirm_mac: &str) -> Result<(), Error> {
+        check_mac_confirmation(&self.get_mac().await?, confirm_mac)?;
         let resp = self.client.http_client
-            .get(&format!("http://{}/cgi-bin/summary.cgi", self.ip))
+            .get(format!("http://{}/cgi-bin/reset_conf.cgi", self.ip))
             .send_with_digest_auth(&self.username, &self.password)
             .await?;
         if resp.status().is_success() {
-            //TODO: We should parse the status and properly return errors
-            let text = resp.text().await?;
-            //println!("response {}", text);
-            let summary: cgi::SummaryResponse = serde_json::from_str(&text)?;
-            if let Some(sum) = summary.summary.get(0) {
-                Ok(sum.rate_5s / 1000.0)
-            } else {
-                // Miner can not return a summary if it is not mining
-                Ok(0.0)
-            }
+            Ok(())
         } else {
             Err(Error::HttpRequestFailed)
         }
     }
 
-    async fn get_power(&self) -> Result<f64, Error> {
+    async fn get_hashrate(&self) -> Result<Hashrate, Error> {
+        Ok(Self::hashrate_from(&self.get_summary().await?))
+    }
+
+    async fn get_power(&self) -> Result<Power, Error> {
         match self.get_hashrate().await {
             Ok(hashrate) => {
                 let model = self.get_model().await?;
-                Ok(hashrate * POWER_MAP.get(model.as_str()).ok_or(Error::UnknownModel(model))?)
+                Ok(hashrate * Self::efficiency_from(model)?)
             },
             Err(e) => Err(e),
         }
     }
 
-    async fn get_efficiency(&self) -> Result<f64, Error> {
-        let model = self.get_model().await?;
-        Ok(*POWER_MAP.get(model.as_str()).ok_or(Error::UnknownModel(model))?)
+    async fn get_efficiency(&self) -> Result<Efficiency, Error> {
+        Self::efficiency_from(self.get_model().await?)
     }
 
-    async fn get_nameplate_rate(&self) -> Result<f64, Error> {
-        let resp = self.client.http_client
-            .get(&format!("http://{}/cgi-bin/stats.cgi", self.ip))
-            .send_with_digest_auth(&self.username, &self.password)
-            .await?;
-        if resp.status().is_success() {
-            let stats = resp.json::<cgi::StatsResponse>().await;
-            let stats = stats?;
-            if let Some(stat) = stats.stats.get(0) {
-                Ok(stat.rate_ideal / 1000.0)
-            } else {
-                //TODO: Decide to return an error or just an empty vector
-                Ok(0.0)
-            }
-        } else {
-            //println!("{:?}", resp);
-            Err(Error::HttpRequestFailed)
-        }
+    async fn get_nameplate_rate(&self) -> Result<Hashrate, Error> {
+        Ok(Self::nameplate_from(&self.get_stats().await?))
     }
 
-    async fn get_temperature(&self) -> Result<f64, Error> {
-        // Antminer doesn't report a single temperature,
-        // instead return the average of the chip sensors
-        let resp = self.client.http_client
-            .get(&format!("http://{}/cgi-bin/stats.cgi", self.ip))
-            .send_with_digest_auth(&self.username, &self.password)
-            .await?;
-        if resp.status().is_success() {
-            let stats: cgi::StatsResponse = resp.json().await?;
-            if let Some(stat) = stats.stats.get(0) {
-                let mut ret = 0.0;
-                let mut ntemp = 0;
-                for chain in &stat.chain {
-                    for temp in &chain.temp_chip {
-                        ntemp += 1;
-                        ret += *temp as f64;
-                    }
-                }
-                Ok(ret / ntemp as f64)
-            } else {
-                //TODO: Decide to return an error or just an empty vector
-                Ok(0.0)
-            }
-        } else {
-            //println!("{:?}", resp);
-            Err(Error::HttpRequestFailed)
-        }
+    async fn get_temperature(&self) -> Result<Temperature, Error> {
+        Ok(Self::temperature_from(&self.get_stats().await?))
     }
 
     async fn get_fan_speed(&self) -> Result<Vec<u32>, Error> {
-        let resp = self.client.http_client
-            .get(&format!("http://{}/cgi-bin/stats.cgi", self.ip))
-            .send_with_digest_auth(&self.username, &self.password)
-            .await?;
-        if resp.status().is_success() {
-            let stats: cgi::StatsResponse = resp.json().await?;
-            if let Some(stat) = stats.stats.get(0) {
-                //TODO: Gotta be a way to avoid this clone
-                Ok(stat.fan.clone())
-            } else {
-                //TODO: Decide to return an error or just an empty vector
-                Ok(vec![])
-            }
+        Ok(Self::fans_from(&self.get_stats().await?))
+    }
+
+    async fn get_fan_config(&self) -> Result<FanConfig, Error> {
+        let conf = self.get_conf().await?;
+        if conf.bitmain_fan_ctrl {
+            let pwm = conf.bitmain_fan_pwm.parse().map_err(|_| Error::InvalidResponse)?;
+            Ok(FanConfig::Fixed(pwm))
         } else {
-            //println!("{:?}", resp);
-            Err(Error::HttpRequestFailed)
+            Ok(FanConfig::Auto)
         }
     }
 
-    async fn get_pools(&self) -> Result<Vec<Pool>, Error> {
+    async fn set_fan_config(&mut self, config: FanConfig) -> Result<(), Error> {
+        config.validate()?;
+        let mut json: SetConf = self.get_conf().await?.into();
+        json.apply_fan(config)?;
+
         let resp = self.client.http_client
-            .get(&format!("http://{}/cgi-bin/get_miner_conf.cgi", self.ip))
+            .post(format!("http://{}/cgi-bin/set_miner_conf.cgi", self.ip))
+            .json(&json)
             .send_with_digest_auth(&self.username, &self.password)
             .await?;
         if resp.status().is_success() {
-            let json = resp.json::<cgi::GetConfResponse>().await?;
-            let pools = json.pools;
-            Ok(pools)
+            Ok(())
         } else {
             Err(Error::HttpRequestFailed)
         }
     }
 
-    async fn set_pools(&mut self, pools: Vec<Pool>) -> Result<(), Error> {
-        let resp = self.client.http_client
-            .get(&format!("http://{}/cgi-bin/get_miner_conf.cgi", self.ip))
-            .send_with_digest_auth(&self.username, &self.password)
-            .await?;
-
-        if !resp.status().is_success() {
-            return Err(Error::HttpRequestFailed);
-        }
+    async fn get_pools(&self) -> Result<Vec<Pool>, Error> {
+        Ok(self.get_conf().await?.pools())
+    }
 
-        let mut json: SetConf = resp.json::<cgi::GetConfResponse>().await?.into();
-        json.pools = pools;
+    async fn set_pools(&mut self, pools: Vec<Pool>) -> Result<(), Error> {
+        validate_pools(&pools, self.max_pools())?;
+        let mut json: SetConf = self.get_conf().await?.into();
+        json.pools = pool_slots(&pools, 3).into_iter().map(cgi::ConfPool::from).collect();
         
         let resp = self.client.http_client
-            .post(&format!("http://{}/cgi-bin/set_miner_conf.cgi", self.ip))
+            .post(format!("http://{}/cgi-bin/set_miner_conf.cgi", self.ip))
             .json(&json)
             .send_with_digest_auth(&self.username, &self.password)
             .await?;
@@ -241,29 +360,90 @@ impl Miner for Antminer {
         }
     }
 
+    async fn get_pool_stats(&self) -> Result<Vec<PoolStats>, Error> {
+        let pools = self.get_pools_cgi().await?;
+        // pools.cgi has no active flag, bmminer mines on the first alive pool by priority
+        let active = pools.pools.iter()
+            .filter(|p| PoolStatus::from_cgminer(&p.status) == PoolStatus::Alive)
+            .min_by_key(|p| p.priority)
+            .map(|p| p.index);
+        Ok(pools.pools.iter().map(|p| PoolStats {
+            url: p.url.clone(),
+            username: p.user.clone(),
+            status: PoolStatus::from_cgminer(&p.status),
+            active: Some(p.index) == active,
+            priority: p.priority,
+            accepted: p.accepted,
+            rejected: p.rejected,
+            stale: p.stale,
+            difficulty_accepted: p.diffa as f64,
+            last_share_time: p.last_share_time(),
+            stratum_difficulty: p.stratum_difficulty(),
+        }).collect())
+    }
+
+    async fn get_share_stats(&self) -> Result<ShareStats, Error> {
+        let (summary, pools) = tokio::try_join!(self.get_summary(), self.get_pools_cgi())?;
+        // Like get_hashrate, a miner that isn't mining has no summary and counts as zero
+        let (hw_all, bestshare) = summary.summary.first().map_or((0, 0), |s| (s.hw_all, s.bestshare));
+        // The cgi only totals shares per pool
+        let pools = pools.pools;
+        let diffa = pools.iter().map(|p| p.diffa).sum::<usize>() as f64;
+        let diffr = pools.iter().map(|p| p.diffr).sum::<usize>() as f64;
+        let diffs = pools.iter().map(|p| p.diffs).sum::<usize>() as f64;
+        let total = diffa + diffr + diffs;
+        let percent = |diff: f64| if total > 0.0 { diff / total * 100.0 } else { 0.0 };
+        Ok(ShareStats {
+            accepted: pools.iter().map(|p| p.accepted).sum(),
+            rejected: pools.iter().map(|p| p.rejected).sum(),
+            stale: Some(pools.iter().map(|p| p.stale).sum()),
+            discarded: Some(pools.iter().map(|p| p.discarded).sum()),
+            hardware_errors: Some(hw_all),
+            best_share: Some(bestshare),
+            difficulty_accepted: Some(diffa),
+            difficulty_rejected: Some(diffr),
+            pool_rejected_percent: Some(percent(diffr)),
+            pool_stale_percent: Some(percent(diffs)),
+            // Only the stats page has this and it's not worth a third request
+            hardware_error_percent: None,
+        })
+    }
+
     async fn get_sleep(&self) -> Result<bool, Error> {
+        Ok(self.get_conf().await?.bitmain_work_mode == "1")
+    }
+
+    async fn set_sleep(&mut self, sleep: bool) -> Result<(), Error> {
         let resp = self.client.http_client
-            .get(&format!("http://{}/cgi-bin/get_miner_conf.cgi", self.ip))
+            .post(format!("http://{}/cgi-bin/set_miner_conf.cgi", self.ip))
+            .json(&json!({
+                "miner-mode": sleep as u8,
+            }))
             .send_with_digest_auth(&self.username, &self.password)
             .await?;
         if resp.status().is_success() {
-            let json = resp.json::<cgi::GetConfResponse>().await?;
-            Ok(json.bitmain_work_mode == "1")
+            //println!("{}", resp.text().await?);
+            Ok(())
         } else {
             Err(Error::HttpRequestFailed)
         }
     }
 
-    async fn set_sleep(&mut self, sleep: bool) -> Result<(), Error> {
+    async fn get_work_mode(&self) -> Result<WorkMode, Error> {
+        Ok(self.get_conf().await?.work_mode())
+    }
+
+    async fn set_work_mode(&mut self, mode: WorkMode) -> Result<(), Error> {
+        let miner_mode = SetConf::miner_mode_from(mode).ok_or(Error::NotSupported)?;
+        let mut json: SetConf = self.get_conf().await?.into();
+        json.miner_mode = miner_mode;
+
         let resp = self.client.http_client
-            .post(&format!("http://{}/cgi-bin/set_miner_conf.cgi", self.ip))
-            .json(&json!({
-                "miner-mode": sleep as u8,
-            }))
+            .post(format!("http://{}/cgi-bin/set_miner_conf.cgi", self.ip))
+            .json(&json)
             .send_with_digest_auth(&self.username, &self.password)
             .await?;
         if resp.status().is_success() {
-            //println!("{}", resp.text().await?);
             Ok(())
         } else {
             Err(Error::HttpRequestFailed)
@@ -272,7 +452,7 @@ impl Miner for Antminer {
 
     async fn get_blink(&self) -> Result<bool, Error> {
         let resp = self.client.http_client
-            .get(&format!("http://{}/cgi-bin/get_blink_status.cgi", self.ip))
+            .get(format!("http://{}/cgi-bin/get_blink_status.cgi", self.ip))
             .send_with_digest_auth(&self.username, &self.password)
             .await?;
         if resp.status().is_success() {
@@ -285,7 +465,7 @@ impl Miner for Antminer {
 
     async fn set_blink(&mut self, blink: bool) -> Result<(), Error> {
         let resp = self.client.http_client
-            .post(&format!("http://{}/cgi-bin/blink.cgi", self.ip))
+            .post(format!("http://{}/cgi-bin/blink.cgi", self.ip))
             .json(&json!({
                 "blink": blink,
             }))
@@ -300,7 +480,7 @@ impl Miner for Antminer {
 
     async fn get_logs(&mut self) -> Result<Vec<String>, Error> {
         let resp = self.client.http_client
-            .get(&format!("http://{}/cgi-bin/log.cgi", self.ip))
+            .get(format!("http://{}/cgi-bin/log.cgi", self.ip))
             .send_with_digest_auth(&self.username, &self.password)
             .await?;
         if resp.status().is_success() {
@@ -310,27 +490,246 @@ impl Miner for Antminer {
         }
     }
 
+    async fn get_log_entries(&mut self) -> Result<Vec<LogEntry>, Error> {
+        Ok(log::parse_antminer(&self.get_logs().await?))
+    }
+
     async fn get_mac(&self) -> Result<String, Error> {
+        Ok(self.get_system_info().await?.macaddr)
+    }
+
+    async fn get_firmware_version(&self) -> Result<String, Error> {
+        Ok(self.get_system_info().await?.system_filesystem_version)
+    }
+
+    async fn get_serial_number(&self) -> Result<String, Error> {
+        // The cgi has no unit serial, only the hashboards'
+        self.get_stats().await?.stats.iter()
+            .flat_map(|stat| stat.chain.iter())
+            .map(|chain| chain.sn.clone())
+            .find(|sn| !sn.is_empty())
+            .ok_or(Error::ExpectedReturn)
+    }
+
+    async fn get_uptime(&self) -> Result<Duration, Error> {
+        let stats = self.get_stats().await?;
+        let stat = stats.stats.first().ok_or(Error::ExpectedReturn)?;
+        Ok(Duration::from_secs(stat.elapsed as u64))
+    }
+
+    async fn get_network_config(&self) -> Result<NetworkConfig, Error> {
+        let sys_info = self.get_system_info().await?;
+        Ok(NetworkConfig {
+            dhcp: sys_info.nettype.eq_ignore_ascii_case("dhcp"),
+            ip: NetworkConfig::addr_from(&sys_info.ipaddress),
+            netmask: NetworkConfig::addr_from(&sys_info.netmask),
+            gateway: NetworkConfig::addr_from(&sys_info.gateway),
+            dns: NetworkConfig::dns_from(&sys_info.dnsservers),
+            hostname: Some(sys_info.hostname),
+        })
+    }
+
+    async fn set_network_config(&mut self, config: NetworkConfig) -> Result<(), Error> {
+        config.validate()?;
+        let hostname = match config.hostname {
+            Some(ref hostname) => hostname.clone(),
+            None => self.get_system_info().await?.hostname,
+        };
+        let opt = |addr: Option<std::net::Ipv4Addr>| addr.map(|a| a.to_string()).unwrap_or_default();
+        // ipPro is 1 for DHCP and 2 for static
+        let json = if config.dhcp {
+            json!({
+                "ipAddress": "",
+                "ipDns": "",
+                "ipGateway": "",
+                "ipHost": hostname,
+                "ipPro": 1,
+                "ipSub": "",
+            })
+        } else {
+            json!({
+                "ipAddress": opt(config.ip),
+                "ipDns": config.dns_to(","),
+                "ipGateway": opt(config.gateway),
+                "ipHost": hostname,
+                "ipPro": 2,
+                "ipSub": opt(config.netmask),
+            })
+        };
         let resp = self.client.http_client
-            .get(&format!("http://{}/cgi-bin/get_system_info.cgi", self.ip))
+            .post(format!("http://{}/cgi-bin/set_network_conf.cgi", self.ip))
+            .json(&json)
             .send_with_digest_auth(&self.username, &self.password)
             .await?;
         if resp.status().is_success() {
-            let sys_info: cgi::SystemInfoResponse = resp.json().await?;
-            Ok(sys_info.macaddr)
+            Ok(())
         } else {
             Err(Error::HttpRequestFailed)
         }
     }
 
-    async fn get_errors(&mut self) -> Result<Vec<String>, Error> {
+    async fn get_errors(&mut self) -> Result<Vec<MinerFault>, Error> {
         let log = self.get_logs().await?.join("\n");
         let mut errors = HashSet::new();
         for err in AntminerErrors.iter() {
-            if let Some(msg) = err.get_msg(&log) {
-                errors.insert(msg);
+            if let Some(fault) = err.get_fault(&log) {
+                errors.insert(fault);
             }
         }
         Ok(errors.into_iter().collect())
     }
+
+    async fn get_snapshot(&self) -> Result<MinerSnapshot, Error> {
+        // The cgi calls are independent, so issue them together
+        let (sys_info, summary, stats, conf) = tokio::try_join!(
+            self.get_system_info(),
+            self.get_summary(),
+            self.get_stats(),
+            self.get_conf(),
+        )?;
+
+        let hashrate = Self::hashrate_from(&summary);
+        // Power is only known for models we have a rated efficiency for, the rest is still worth returning
+        let efficiency = Self::efficiency_from(Self::model_from(&sys_info)).ok();
+        Ok(MinerSnapshot {
+            model: Self::model_from(&sys_info),
+            mac: sys_info.macaddr,
+            hashrate,
+            power: efficiency.map(|e| hashrate * e),
+            power_estimated: true,
+            efficiency,
+            nameplate_rate: Self::nameplate_from(&stats),
+            temperature: Self::temperature_from(&stats),
+            fan_speed: Self::fans_from(&stats),
+            pools: Some(conf.pools()),
+            sleep: Some(conf.bitmain_work_mode == "1"),
+        })
+    }
+
+    async fn get_hashboards(&self) -> Result<Vec<Hashboard>, Error> {
+        let stats = self.get_stats().await?;
+        if let Some(stat) = stats.stats.get(0) {
+            Ok(stat.chain.iter().map(|chain| Hashboard {
+                index: chain.index,
+                online: chain.asic_num > 0,
+                hashrate: Some(Self::rate_from(chain.rate_real, &stat.rate_unit)),
+                ideal_rate: Some(Self::rate_from(chain.rate_ideal, &stat.rate_unit)),
+                chip_count: Some(chain.asic_num),
+                chip_temp: chain.temp_chip.iter().max().map(|t| Temperature::from_celsius(*t as f64)),
+                pcb_temp: chain.temp_pcb.iter().max().map(|t| Temperature::from_celsius(*t as f64)),
+                frequency: Some(chain.freq_avg as f64),
+                hw_errors: Some(chain.hw as usize),
+                serial: Some(chain.sn.clone()).filter(|sn| !sn.is_empty()),
+            }).collect())
+        } else {
+            Ok(vec![])
+        }
+    }
+
+    async fn export_config(&self) -> Result<MinerConfig, Error> {
+        let mut config = export_common(self).await?;
+        let conf: SetConf = self.get_conf().await?.into();
+        config.vendor_settings = Some(serde_json::to_value(conf)?);
+        Ok(config)
+    }
+
+    async fn import_config(&mut self, config: MinerConfig) -> Result<(), Error> {
+        let mut vendor_settings = match (&config.vendor_settings, config.vendor == self.get_type()) {
+            (Some(settings), true) => serde_json::from_value::<SetConf>(settings.clone())?,
+            _ => return import_common(self, config).await,
+        };
+        vendor_settings.apply_config(&config)?;
+        // The miner config is restored in one go, the network is separate
+        let resp = self.client.http_client
+            .post(format!("http://{}/cgi-bin/set_miner_conf.cgi", self.ip))
+            .json(&vendor_settings)
+            .send_with_digest_auth(&self.username, &self.password)
+            .await?;
+        if !resp.status().is_success() {
+            return Err(Error::HttpRequestFailed);
+        }
+        if let Some(network) = config.network {
+            self.set_network_config(network).await?;
+        }
+        Ok(())
+    }
+
+    async fn upgrade_firmware(&mut self, image: &[u8], progress: &(dyn Fn(UpgradeStage) + Send + Sync)) -> Result<(), Error> {
+        progress(UpgradeStage::Uploading);
+        let url = format!("http://{}/cgi-bin/upgrade.cgi", self.ip);
+        // Take the digest challenge from a bodyless request so the image only goes up once
+        let auth = digest_header(&self.client.http_client, &url, "POST", &self.username, &self.password).await?;
+        let (content_type, body) = multipart::file_form("firmware", "firmware.tar.gz", image);
+        let len = body.len();
+        let (body, mut uploaded) = upload::tracked_body(body);
+        let mut req = self.client.http_client
+            .post(url)
+            .header("Content-Type", content_type)
+            .header("Content-Length", len)
+            .body(body)
+            .timeout(UPGRADE_TIMEOUT);
+        if let Some(auth) = auth {
+            req = req.header("Authorization", auth);
+        }
+        // The CGI flashes before responding, then reboots on its own
+        let send = req.send();
+        tokio::pin!(send);
+        let mut flashing = false;
+        let resp = loop {
+            tokio::select! {
+                resp = &mut send => break resp?,
+                done = &mut uploaded, if !flashing => {
+                    flashing = true;
+                    // An error means the body was dropped, the send fails with the reason
+                    if done.is_ok() {
+                        progress(UpgradeStage::Flashing);
+                    }
+                },
+            }
+        };
+        if !resp.status().is_success() {
+            return Err(Error::HttpRequestFailed);
+        }
+        progress(UpgradeStage::Rebooting);
+        self.client.wait_for_reboot(&self.ip, 80).await?;
+        progress(UpgradeStage::Online);
+        Ok(())
+    }
+
+    async fn get_tuning(&self) -> Result<Tuning, Error> {
+        let conf = self.get_conf().await?;
+        let stats = self.get_stats().await?;
+        let boards = match stats.stats.get(0) {
+            Some(stat) => stat.chain.iter().map(|chain| BoardTuning {
+                index: chain.index,
+                target: None,
+                frequency: Some(chain.freq_avg as f64),
+            }).collect(),
+            None => vec![],
+        };
+        Ok(Tuning {
+            global: conf.tuning(),
+            boards,
+        })
+    }
+
+    async fn set_tuning(&mut self, board: Option<usize>, tuning: TuningConfig) -> Result<(), Error> {
+        // Stock firmware only has global settings
+        if board.is_some() || tuning.frequency_offset.is_some() {
+            return Err(Error::NotSupported);
+        }
+        let mut json: SetConf = self.get_conf().await?.into();
+        json.apply_tuning(&tuning);
+
+        let resp = self.client.http_client
+            .post(format!("http://{}/cgi-bin/set_miner_conf.cgi", self.ip))
+            .json(&json)
+            .send_with_digest_auth(&self.username, &self.password)
+            .await?;
+        if resp.status().is_success() {
+            Ok(())
+        } else {
+            Err(Error::HttpRequestFailed)
+        }
+    }
 }
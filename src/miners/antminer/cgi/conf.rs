@@ -1,5 +1,24 @@
 use serde::{Deserialize, Serialize};
-use crate::Pool;
+use crate::error::Error;
+use crate::miner::{pool_slots, skip_unsupported, validate_pools, FanConfig, MinerConfig, Pool, PoolSlot, TuningConfig, WorkMode};
+
+/// A pool slot as the cgi stores it, unused slots have an empty url
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ConfPool {
+    pub url: String,
+    pub user: String,
+    pub pass: Option<String>,
+}
+
+impl From<PoolSlot> for ConfPool {
+    fn from(slot: PoolSlot) -> Self {
+        ConfPool {
+            url: slot.url,
+            user: slot.username,
+            pass: Some(slot.password),
+        }
+    }
+}
 
 #[derive(Deserialize, Debug)]
 pub struct GetConfResponse {
@@ -27,35 +46,236 @@ pub struct GetConfResponse {
     pub bitmain_use_vil: bool,
     #[serde(rename = "bitmain-voltage")]
     pub bitmain_voltage: String,
-    /// "0" is normal, "1" is sleep
+    /// "0" is normal, "1" is sleep, "3" is low power
     #[serde(rename = "bitmain-work-mode")]
     pub bitmain_work_mode: String,
-    pub pools: Vec<Pool>,
+    pub pools: Vec<ConfPool>,
 }
 
-#[derive(Serialize, Debug)]
+impl GetConfResponse {
+    pub fn pools(&self) -> Vec<Pool> {
+        self.pools.iter()
+            .filter_map(|p| Pool::from_slot(&p.url, &p.user, p.pass.as_deref()))
+            .collect()
+    }
+
+    /// Antminers sometimes have this empty, treat it as normal like SetConf does
+    pub fn work_mode(&self) -> WorkMode {
+        match self.bitmain_work_mode.parse().unwrap_or(0) {
+            0 => WorkMode::Normal,
+            1 => WorkMode::Sleep,
+            3 => WorkMode::LowPower,
+            n => WorkMode::Vendor(n),
+        }
+    }
+
+    /// Both are empty while the stock profile is in use
+    pub fn tuning(&self) -> TuningConfig {
+        TuningConfig {
+            frequency: self.bitmain_freq.parse().ok(),
+            frequency_offset: None,
+            voltage: self.bitmain_voltage.parse::<f64>().ok().map(|v| v / 100.0),
+        }
+    }
+}
+
+/// Everything set_miner_conf.cgi takes, round trips the whole of GetConfResponse
+#[derive(Serialize, Deserialize, Debug)]
 pub struct SetConf {
+    #[serde(rename = "api-allow")]
+    pub api_allow: String,
+    #[serde(rename = "api-groups")]
+    pub api_groups: String,
+    #[serde(rename = "api-listen")]
+    pub api_listen: bool,
+    #[serde(rename = "api-network")]
+    pub api_network: bool,
+    #[serde(rename = "bitmain-ccdelay")]
+    pub bitmain_ccdelay: String,
     #[serde(rename = "bitmain-fan-ctrl")]
     pub bitmain_fan_ctrl: bool,
     #[serde(rename = "bitmain-fan-pwm")]
     pub bitmain_fan_pwm: String,
     #[serde(rename = "freq-level")]
     pub freq_level: String,
-    /// 0 is normal, 1 is sleep
+    /// Chip frequency in MHz, empty for the stock profile
+    #[serde(rename = "bitmain-freq")]
+    pub bitmain_freq: String,
+    /// Board voltage in hundredths of a volt, empty for the stock profile
+    #[serde(rename = "bitmain-voltage")]
+    pub bitmain_voltage: String,
+    #[serde(rename = "bitmain-pwth")]
+    pub bitmain_pwth: String,
+    #[serde(rename = "bitmain-use-vil")]
+    pub bitmain_use_vil: bool,
+    /// 0 is normal, 1 is sleep, 3 is low power
     #[serde(rename = "miner-mode")]
     pub miner_mode: u8,
-    pub pools: Vec<Pool>,
+    pub pools: Vec<ConfPool>,
 }
 
 impl From<GetConfResponse> for SetConf {
     fn from(conf: GetConfResponse) -> Self {
         SetConf {
+            api_allow: conf.api_allow,
+            api_groups: conf.api_groups,
+            api_listen: conf.api_listen,
+            api_network: conf.api_network,
+            bitmain_ccdelay: conf.bitmain_ccdelay,
             bitmain_fan_ctrl: conf.bitmain_fan_ctrl,
             bitmain_fan_pwm: conf.bitmain_fan_pwm,
             freq_level: conf.bitmain_freq_level,
+            bitmain_freq: conf.bitmain_freq,
+            bitmain_voltage: conf.bitmain_voltage,
+            bitmain_pwth: conf.bitmain_pwth,
+            bitmain_use_vil: conf.bitmain_use_vil,
             // Antminers sometimes have this empty, default to 0 (normal)
             miner_mode: conf.bitmain_work_mode.parse().unwrap_or(0),
             pools: conf.pools,
         }
     }
 }
+
+impl SetConf {
+    /// The miner-mode for a work mode, None for high performance which stock firmware doesn't have
+    pub fn miner_mode_from(mode: WorkMode) -> Option<u8> {
+        match mode {
+            WorkMode::Normal => Some(0),
+            WorkMode::Sleep => Some(1),
+            WorkMode::LowPower => Some(3),
+            WorkMode::Vendor(n) => Some(n),
+            WorkMode::HighPerformance => None,
+        }
+    }
+
+    /// Overwrite the frequency and voltage, fields left as None keep their current value
+    pub fn apply_tuning(&mut self, tuning: &TuningConfig) {
+        if let Some(freq) = tuning.frequency {
+            self.bitmain_freq = (freq.round() as u32).to_string();
+        }
+        if let Some(voltage) = tuning.voltage {
+            self.bitmain_voltage = ((voltage * 100.0).round() as u32).to_string();
+        }
+    }
+
+    /// Stock firmware will not hash with the fans unplugged, so there's no immersion mode
+    pub fn apply_fan(&mut self, config: FanConfig) -> Result<(), Error> {
+        config.validate()?;
+        match config {
+            FanConfig::Auto => self.bitmain_fan_ctrl = false,
+            FanConfig::Fixed(pwm) => {
+                self.bitmain_fan_ctrl = true;
+                self.bitmain_fan_pwm = pwm.to_string();
+            },
+            FanConfig::Immersion => return Err(Error::NotSupported),
+        }
+        Ok(())
+    }
+
+    /// Lay the neutral fields of a backup over its vendor settings, skipping what stock firmware can't take
+    pub fn apply_config(&mut self, config: &MinerConfig) -> Result<(), Error> {
+        if let Some(pools) = &config.pools {
+            validate_pools(pools, Some(3))?;
+            self.pools = pool_slots(pools, 3).into_iter().map(ConfPool::from).collect();
+        }
+        if let Some(mode) = config.work_mode {
+            let res = Self::miner_mode_from(mode).map(|mode| self.miner_mode = mode).ok_or(Error::NotSupported);
+            skip_unsupported("work mode", res)?;
+        }
+        if let Some(fan) = config.fan {
+            skip_unsupported("fan config", self.apply_fan(fan))?;
+        }
+        if let Some(tuning) = &config.tuning {
+            // Stock firmware only takes absolute values
+            if tuning.frequency_offset.is_some() {
+                skip_unsupported("tuning", Err(Error::NotSupported))?;
+            } else {
+                self.apply_tuning(tuning);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONF: &str = r#"{"pools":[{"url":"stratum+tcp://btc.pool.com:3333","user":"worker.1","pass":"x"},{"url":"","user":"","pass":""},{"url":"","user":"","pass":""}],"api-listen":true,"api-network":false,"api-groups":"A:stats:pools:devs:summary:version","api-allow":"A:0/0,W:*","bitmain-fan-ctrl":false,"bitmain-fan-pwm":"100","bitmain-use-vil":true,"bitmain-freq":"675","bitmain-voltage":"1380","bitmain-ccdelay":"0","bitmain-pwth":"0","bitmain-work-mode":"0","bitmain-freq-level":"100"}"#;
+
+    #[test]
+    fn it_maps_work_modes() {
+        let conf: GetConfResponse = serde_json::from_str(CONF).unwrap();
+        assert_eq!(conf.work_mode(), WorkMode::Normal);
+        for (raw, mode) in [("1", WorkMode::Sleep), ("3", WorkMode::LowPower), ("", WorkMode::Normal), ("4", WorkMode::Vendor(4))] {
+            let conf: GetConfResponse = serde_json::from_str(&CONF.replace(r#""bitmain-work-mode":"0""#, &format!(r#""bitmain-work-mode":"{}""#, raw))).unwrap();
+            assert_eq!(conf.work_mode(), mode);
+            // What set_work_mode writes reads back as the same mode
+            if !raw.is_empty() {
+                assert_eq!(SetConf::miner_mode_from(mode).map(|m| m.to_string()).as_deref(), Some(raw));
+            }
+        }
+        assert_eq!(SetConf::miner_mode_from(WorkMode::HighPerformance), None);
+    }
+
+    #[test]
+    fn it_converts_voltage_hundredths() {
+        let conf: GetConfResponse = serde_json::from_str(CONF).unwrap();
+        let tuning = conf.tuning();
+        assert_eq!(tuning.frequency, Some(675.0));
+        assert_eq!(tuning.voltage, Some(13.8));
+
+        let mut set: SetConf = conf.into();
+        set.apply_tuning(&TuningConfig { frequency: None, frequency_offset: None, voltage: Some(13.55) });
+        assert_eq!(set.bitmain_voltage, "1355");
+        assert_eq!(set.bitmain_freq, "675");
+
+        // The stock profile leaves both empty
+        let stock: GetConfResponse = serde_json::from_str(&CONF.replace("1380", "")).unwrap();
+        assert_eq!(stock.tuning().voltage, None);
+    }
+
+    #[test]
+    fn it_round_trips_set_conf() {
+        let conf: GetConfResponse = serde_json::from_str(CONF).unwrap();
+        let set: SetConf = conf.into();
+        // What export_config stores as vendor_settings and import_config posts back
+        let backup = serde_json::to_value(&set).unwrap();
+        assert_eq!(backup["api-allow"], "A:0/0,W:*");
+        assert_eq!(backup["bitmain-freq"], "675");
+        assert_eq!(backup["bitmain-voltage"], "1380");
+        assert_eq!(backup["miner-mode"], 0);
+        let restored: SetConf = serde_json::from_value(backup.clone()).unwrap();
+        assert_eq!(restored.api_allow, set.api_allow);
+        assert_eq!(serde_json::to_value(&restored).unwrap(), backup);
+    }
+
+    #[test]
+    fn it_applies_neutral_fields_over_vendor_settings() {
+        let conf: GetConfResponse = serde_json::from_str(CONF).unwrap();
+        let mut set: SetConf = conf.into();
+        let config = MinerConfig {
+            vendor: "Antminer".to_string(),
+            pools: Some(vec![Pool::new("stratum+tcp://backup.pool.com:3333", "worker.2", None).unwrap()]),
+            work_mode: Some(WorkMode::LowPower),
+            fan: Some(FanConfig::Fixed(80)),
+            tuning: Some(TuningConfig { frequency: None, frequency_offset: Some(5), voltage: None }),
+            network: None,
+            vendor_settings: None,
+        };
+        set.apply_config(&config).unwrap();
+        assert_eq!(set.pools[0].url, "stratum+tcp://backup.pool.com:3333");
+        assert_eq!(set.pools[1].url, "");
+        assert_eq!(set.miner_mode, 3);
+        assert!(set.bitmain_fan_ctrl);
+        assert_eq!(set.bitmain_fan_pwm, "80");
+        // Relative tuning is skipped, the vendor settings stand
+        assert_eq!(set.bitmain_freq, "675");
+        // Settings without a neutral field are kept
+        assert_eq!(set.api_allow, "A:0/0,W:*");
+
+        let immersion = MinerConfig { fan: Some(FanConfig::Immersion), ..config };
+        assert!(set.apply_config(&immersion).is_ok());
+        assert_eq!(set.bitmain_fan_pwm, "80");
+    }
+}
@@ -1,4 +1,5 @@
 use serde::Deserialize;
+use chrono::{DateTime, Duration, Utc};
 
 use crate::miners::antminer::cgi::{Status, CgiInfo};
 
@@ -23,10 +24,47 @@ pub struct PoolStat {
     pub user: String,
 }
 
+impl PoolStat {
+    /// lstime is the time since the last share as h:mm:ss, "0" or empty before the first share
+    /// Being relative, it's taken from our clock rather than the miner's
+    pub fn last_share_time(&self) -> Option<DateTime<Utc>> {
+        let mut parts = self.lstime.split(':').map(|p| p.trim().parse::<i64>());
+        let (h, m, s) = (parts.next()?.ok()?, parts.next()?.ok()?, parts.next()?.ok()?);
+        Some(Utc::now() - Duration::seconds(h * 3600 + m * 60 + s))
+    }
+
+    /// diff is abbreviated, e.g. "65.5K"
+    pub fn stratum_difficulty(&self) -> Option<f64> {
+        let diff = self.diff.trim();
+        let (value, scale) = match diff.chars().last()? {
+            'K' | 'k' => (&diff[..diff.len() - 1], 1e3),
+            'M' => (&diff[..diff.len() - 1], 1e6),
+            'G' => (&diff[..diff.len() - 1], 1e9),
+            'T' => (&diff[..diff.len() - 1], 1e12),
+            _ => (diff, 1.0),
+        };
+        value.trim().parse::<f64>().ok().map(|v| v * scale)
+    }
+}
+
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "UPPERCASE")]
 pub struct PoolsResponse {
     pub info: CgiInfo,
     pub pools: Vec<PoolStat>,
     pub status: Status,
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_parses_pool_stat() {
+        let stat: PoolStat = serde_json::from_str(r#"{"index":0,"url":"stratum+tcp://btc.pool:3333","user":"worker.1","status":"Alive","priority":0,"getworks":120,"accepted":3000,"rejected":2,"discarded":400,"stale":1,"diff":"65.5K","diff1":0,"diffa":196608000,"diffr":131072,"diffs":65536,"lsdiff":65536,"lstime":"0:00:12"}"#).unwrap();
+        assert_eq!(stat.stratum_difficulty(), Some(65500.0));
+        let last_share = stat.last_share_time().unwrap();
+        let ago = Utc::now() - last_share;
+        assert!(ago >= Duration::seconds(12) && ago < Duration::seconds(20));
+    }
+}
@@ -1,51 +1,73 @@
 use lazy_regex::regex;
 
-use crate::miner::MinerError;
+use crate::miner::{MinerError, FaultCategory, FaultSeverity};
 
 pub static AntminerErrors: [MinerError; 11] = [
     // Unsure
     MinerError {
         re: regex!(r".+load chain ([0-9]).+\n.+(EEPROM error|bad_asic_crc)"),
         msg: "Chain {} EEPROM CRC error",
+        category: FaultCategory::Eeprom,
+        severity: FaultSeverity::Error,
     },
     MinerError {
         re: regex!(r"Data load fail for chain ([0-9])"),
         msg: "Chain {} load EEPROM fail",
+        category: FaultCategory::Eeprom,
+        severity: FaultSeverity::Error,
     },
     MinerError {
         re: regex!(r".+ERROR_POWER_LOST"),
         msg: "Power lost",
+        category: FaultCategory::Power,
+        severity: FaultSeverity::Critical,
     },
     MinerError {
         re: regex!(r".+ERROR_FAN_LOST"),
         msg: "Fan lost",
+        category: FaultCategory::Fan,
+        severity: FaultSeverity::Critical,
     },
     MinerError {
         re: regex!(r".+ERROR_TEMP_TOO_HIGH"),
         msg: "Temperature too high",
+        category: FaultCategory::Temperature,
+        severity: FaultSeverity::Critical,
     },
     MinerError {
         re: regex!(r".+_read_an6_voltage"),
         msg: "Read voltage failed",
+        category: FaultCategory::Power,
+        severity: FaultSeverity::Error,
     },
     MinerError {
         re: regex!(r".+Chain ([0-9]) only find ([0-9]+) asic"),
         msg: "Chain {} only find {} asic",
+        category: FaultCategory::Chip,
+        severity: FaultSeverity::Error,
     },
     MinerError {
         re: regex!(r".+i2c: timeout waiting for bus ready"),
         msg: "I2C timeout",
+        category: FaultCategory::ControlBoard,
+        severity: FaultSeverity::Error,
     },
     MinerError {
         re: regex!(r".+fail to read pic temp for chain ([0-9])"),
         msg: "Chain {} read pic temp fail",
+        category: FaultCategory::Hashboard,
+        severity: FaultSeverity::Error,
     },
     MinerError {
         re: regex!(r".+FW type (.+), (?:.+) shows (.+)"),
         msg: "Incorrect firmware (should be {}, found {})",
+        category: FaultCategory::Firmware,
+        severity: FaultSeverity::Critical,
     },
     MinerError {
         re: regex!(r".+read temp sensor failed: chain = ([0-9])"),
         msg: "Chain {} read temp sensor failed",
+        category: FaultCategory::Temperature,
+        severity: FaultSeverity::Error,
     }
 ];
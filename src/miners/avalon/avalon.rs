@@ -1,10 +1,12 @@
 use async_trait::async_trait;
 use serde_json::json;
 use lazy_regex::regex;
+use std::time::Duration;
 
-use crate::miner::{Miner, Pool};
+use crate::miner::{BoardTuning, Capability, Environment, FanConfig, Hashboard, Miner, MinerFault, MinerSnapshot, Pool, PsuInfo, Tuning, TuningConfig, WorkMode};
 use crate::miners::avalon::cgminer;
 use crate::error::Error;
+use crate::units::{Efficiency, Hashrate, Power, Temperature};
 use crate::Client;
 
 pub struct Avalon {
@@ -31,6 +33,34 @@ impl Miner for Avalon {
         "Avalon"
     } 
 
+    fn capabilities(&self) -> &'static [Capability] {
+        &[
+            Capability::ReadSleep,
+            Capability::WriteSleep,
+            Capability::ReadBlink,
+            Capability::WriteBlink,
+            Capability::MeasuredPower,
+            Capability::Hashboards,
+            Capability::ReadPowerLimit,
+            Capability::ReadWorkMode,
+            Capability::WriteWorkMode,
+            Capability::WriteFanConfig,
+            Capability::ReadTuning,
+            Capability::WriteTuning,
+            Capability::BoardTuning,
+            Capability::FirmwareVersion,
+            Capability::SerialNumber,
+            Capability::Uptime,
+            Capability::PsuInfo,
+            Capability::Environment,
+        ]
+    }
+
+    fn max_pools(&self) -> Option<usize> {
+        // cgminer's limit, though pools can't be written through this backend yet
+        Some(3)
+    }
+
     async fn get_model(&self) -> Result<String, Error> {
         let cmd = r#"{"command":"version"}"#;
         let resp = self.client.send_recv(&self.ip, self.port, &cmd).await?;
@@ -60,34 +90,34 @@ impl Miner for Avalon {
         self.client.send(&self.ip, self.port, &cmd).await
     }
 
-    async fn get_hashrate(&self) -> Result<f64, Error> {
+    async fn get_hashrate(&self) -> Result<Hashrate, Error> {
         let cmd = r#"{"command":"estats"}"#;
         let resp = self.client.send_recv(&self.ip, self.port, &cmd).await?;
         let estats = cgminer::EStats::try_from(&serde_json::from_str::<cgminer::StatsResp>(&resp)?)?;
-        Ok(estats.ghs_mm / 1000.0)
+        Ok(Hashrate::from_ghs(estats.ghs_mm))
     }
 
-    async fn get_power(&self) -> Result<f64, Error> {
+    async fn get_power(&self) -> Result<Power, Error> {
         let cmd = r#"{"command":"ascset","parameter":"0,hashpower"}"#;
         let resp = self.client.send_recv(&self.ip, self.port, &cmd).await?;
         let psinfo = cgminer::PowerSupplyInfo::try_from(serde_json::from_str::<cgminer::StatusResp>(&resp)?)?;
-        Ok(psinfo.power as f64)
+        Ok(Power::from_watts(psinfo.power as f64))
     }
 
-    async fn get_efficiency(&self) -> Result<f64, Error> {
+    async fn get_efficiency(&self) -> Result<Efficiency, Error> {
         let cmd = r#"{"command":"estats"}"#;
         let resp = self.client.send_recv(&self.ip, self.port, &cmd).await?;
         let estats = cgminer::EStats::try_from(&serde_json::from_str::<cgminer::StatsResp>(&resp)?)?;
-        Ok(estats.ps.power as f64 / (estats.ghs_mm / 1000.0))
+        (Power::from_watts(estats.ps.power as f64) / Hashrate::from_ghs(estats.ghs_mm)).ok_or(Error::NotHashing)
     }
 
-    async fn get_nameplate_rate(&self) -> Result<f64, Error> {
+    async fn get_nameplate_rate(&self) -> Result<Hashrate, Error> {
         let cmd = r#"{"command":"version"}"#;
         let resp = self.client.send_recv(&self.ip, self.port, &cmd).await?;
         let version = serde_json::from_str::<cgminer::VersionResp>(&resp)?;
         if let Some(version) = version.version {
             if let Some(version) = version.get(0) {
-                Ok(version.hashrate_th()?)
+                Ok(Hashrate::from_ths(version.hashrate_th()?))
             } else {
                 Err(Error::ApiCallFailed("version".to_string()))
             }
@@ -96,11 +126,42 @@ impl Miner for Avalon {
         }
     }
 
-    async fn get_temperature(&self) -> Result<f64, Error> {
+    async fn get_temperature(&self) -> Result<Temperature, Error> {
+        let cmd = r#"{"command":"estats"}"#;
+        let resp = self.client.send_recv(&self.ip, self.port, &cmd).await?;
+        let estats = cgminer::EStats::try_from(&serde_json::from_str::<cgminer::StatsResp>(&resp)?)?;
+        Ok(Temperature::from_celsius(estats.temp as f64))
+    }
+
+    async fn get_psu_info(&self) -> Result<PsuInfo, Error> {
+        let cmd = r#"{"command":"estats"}"#;
+        let resp = self.client.send_recv(&self.ip, self.port, &cmd).await?;
+        let estats = cgminer::EStats::try_from(&serde_json::from_str::<cgminer::StatsResp>(&resp)?)?;
+        // PS only reports the output side
+        Ok(PsuInfo {
+            input_voltage: None,
+            output_voltage: Some(estats.ps.volt_hash as f64),
+            current: Some(estats.ps.current as f64),
+            power: Some(Power::from_watts(estats.ps.power as f64)),
+            fan_speed: None,
+        })
+    }
+
+    async fn get_environment(&self) -> Result<Environment, Error> {
         let cmd = r#"{"command":"estats"}"#;
         let resp = self.client.send_recv(&self.ip, self.port, &cmd).await?;
         let estats = cgminer::EStats::try_from(&serde_json::from_str::<cgminer::StatsResp>(&resp)?)?;
-        Ok(estats.temp as f64)
+        Ok(Environment {
+            inlet_temp: Some(Temperature::from_celsius(estats.temp as f64)),
+            outlet_temp: None,
+        })
+    }
+
+    async fn get_power_limit(&self) -> Result<Power, Error> {
+        let cmd = r#"{"command":"estats"}"#;
+        let resp = self.client.send_recv(&self.ip, self.port, &cmd).await?;
+        let estats = cgminer::EStats::try_from(&serde_json::from_str::<cgminer::StatsResp>(&resp)?)?;
+        estats.mpo.map(|mpo| Power::from_watts(mpo as f64)).ok_or(Error::NotSupported)
     }
 
     async fn get_fan_speed(&self) -> Result<Vec<u32>, Error> {
@@ -115,6 +176,25 @@ impl Miner for Avalon {
         ])
     }
 
+    async fn set_fan_config(&mut self, config: FanConfig) -> Result<(), Error> {
+        config.validate()?;
+        let speed = match config {
+            FanConfig::Auto => -1,
+            FanConfig::Fixed(pct) => pct as i32,
+            FanConfig::Immersion => return Err(Error::NotSupported),
+        };
+        let cmd = json!({
+            "command": "ascset",
+            "parameter": format!("0,fan-spd,{}", speed),
+        });
+        let resp = self.client.send_recv(&self.ip, self.port, &cmd).await?;
+        let status = serde_json::from_str::<cgminer::StatusResp>(&resp)?;
+        match status.status[0].status {
+            cgminer::StatusCode::SUCC | cgminer::StatusCode::INFO => Ok(()),
+            _ => Err(Error::ApiCallFailed(status.status[0].msg.clone())),
+        }
+    }
+
     async fn get_pools(&self) -> Result<Vec<Pool>, Error> {
         Err(Error::NotSupported)
     }
@@ -147,6 +227,41 @@ impl Miner for Avalon {
         }
     }
 
+    async fn get_work_mode(&self) -> Result<WorkMode, Error> {
+        let cmd = r#"{"command":"estats"}"#;
+        let resp = self.client.send_recv(&self.ip, self.port, &cmd).await?;
+        let estats = cgminer::EStats::try_from(&serde_json::from_str::<cgminer::StatsResp>(&resp)?)?;
+        if estats.ps.power == 0 {
+            return Ok(WorkMode::Sleep);
+        }
+        match estats.workmode.ok_or(Error::NotSupported)? {
+            0 => Ok(WorkMode::LowPower),
+            1 => Ok(WorkMode::Normal),
+            2 => Ok(WorkMode::HighPerformance),
+            n => Ok(WorkMode::Vendor(n)),
+        }
+    }
+
+    async fn set_work_mode(&mut self, mode: WorkMode) -> Result<(), Error> {
+        let workmode = match mode {
+            WorkMode::Sleep => return self.set_sleep(true).await,
+            WorkMode::LowPower => 0,
+            WorkMode::Normal => 1,
+            WorkMode::HighPerformance => 2,
+            WorkMode::Vendor(n) => n,
+        };
+        let cmd = json!({
+            "command": "ascset",
+            "parameter": format!("0,workmode,set,{}", workmode),
+        });
+        let resp = self.client.send_recv(&self.ip, self.port, &cmd).await?;
+        let status = serde_json::from_str::<cgminer::StatusResp>(&resp)?;
+        match status.status[0].status {
+            cgminer::StatusCode::SUCC | cgminer::StatusCode::INFO => Ok(()),
+            _ => Err(Error::ApiCallFailed(status.status[0].msg.clone())),
+        }
+    }
+
     async fn get_blink(&self) -> Result<bool, Error> {
         let cmd = r#"{"command":"ascset","parameter":"0,led,1-255"}"#;
         let resp = self.client.send_recv(&self.ip, self.port, &cmd).await?;
@@ -194,7 +309,134 @@ impl Miner for Avalon {
         }
     }
 
-    async fn get_errors(&mut self) -> Result<Vec<String>, Error> {
+    async fn get_firmware_version(&self) -> Result<String, Error> {
+        let cmd = r#"{"command":"version"}"#;
+        let resp = self.client.send_recv(&self.ip, self.port, &cmd).await?;
+        let version = serde_json::from_str::<cgminer::VersionResp>(&resp)?;
+        match version.version.as_ref().and_then(|v| v.first()) {
+            Some(version) => Ok(version.version.clone()),
+            None => Err(Error::ApiCallFailed("version".to_string())),
+        }
+    }
+
+    async fn get_serial_number(&self) -> Result<String, Error> {
+        // Avalons identify by the controller's DNA
+        let cmd = r#"{"command":"version"}"#;
+        let resp = self.client.send_recv(&self.ip, self.port, &cmd).await?;
+        let version = serde_json::from_str::<cgminer::VersionResp>(&resp)?;
+        match version.version.as_ref().and_then(|v| v.first()) {
+            Some(version) => Ok(version.dna.clone()),
+            None => Err(Error::ApiCallFailed("version".to_string())),
+        }
+    }
+
+    async fn get_uptime(&self) -> Result<Duration, Error> {
+        let cmd = r#"{"command":"estats"}"#;
+        let resp = self.client.send_recv(&self.ip, self.port, &cmd).await?;
+        let estats = cgminer::EStats::try_from(&serde_json::from_str::<cgminer::StatsResp>(&resp)?)?;
+        Ok(Duration::from_secs(estats.elapsed))
+    }
+
+    async fn get_errors(&mut self) -> Result<Vec<MinerFault>, Error> {
         Err(Error::NotSupported)
     }
+
+    async fn get_snapshot(&self) -> Result<MinerSnapshot, Error> {
+        // cgminer lets us join commands, so this is a single round-trip
+        let resp = self.client.send_recv(&self.ip, self.port, &cgminer::SnapshotResp::get_cmd()).await?;
+        let snapshot = serde_json::from_str::<cgminer::SnapshotResp>(&resp)?;
+        let [version] = snapshot.version;
+        let [estats] = snapshot.estats;
+        let estats = cgminer::EStats::try_from(&estats)?;
+        let version = version.version
+            .and_then(|v| v.into_iter().next())
+            .ok_or(Error::ApiCallFailed("version".to_string()))?;
+        let hashrate = Hashrate::from_ghs(estats.ghs_mm);
+        let power = Power::from_watts(estats.ps.power as f64);
+        Ok(MinerSnapshot {
+            model: version.model()?.to_string(),
+            mac: version.mac_addr(),
+            hashrate,
+            power: Some(power),
+            power_estimated: false,
+            efficiency: power / hashrate,
+            nameplate_rate: Hashrate::from_ths(version.hashrate_th()?),
+            temperature: Temperature::from_celsius(estats.temp as f64),
+            fan_speed: vec![
+                estats.fan1,
+                estats.fan2,
+                estats.fan3,
+                estats.fan4,
+            ],
+            pools: None,
+            sleep: Some(estats.ps.power == 0),
+        })
+    }
+
+    async fn get_hashboards(&self) -> Result<Vec<Hashboard>, Error> {
+        let cmd = r#"{"command":"estats"}"#;
+        let resp = self.client.send_recv(&self.ip, self.port, &cmd).await?;
+        let estats = cgminer::EStats::try_from(&serde_json::from_str::<cgminer::StatsResp>(&resp)?)?;
+        Ok((0..estats.sys_status.nboards as usize).map(|i| {
+            let hashrate = estats.mghs.get(i).map(|r| Hashrate::from_ghs(*r));
+            Hashboard {
+                index: i,
+                online: hashrate.unwrap_or_default() > Hashrate::default(),
+                hashrate,
+                ideal_rate: None,
+                chip_count: None,
+                chip_temp: estats.mtmax.get(i).map(|t| Temperature::from_celsius(*t as f64)),
+                pcb_temp: None,
+                // Avalon only reports a single frequency for all boards
+                frequency: Some(estats.freq),
+                hw_errors: None,
+                serial: None,
+            }
+        }).collect())
+    }
+
+    async fn get_tuning(&self) -> Result<Tuning, Error> {
+        let cmd = r#"{"command":"estats"}"#;
+        let resp = self.client.send_recv(&self.ip, self.port, &cmd).await?;
+        let estats = cgminer::EStats::try_from(&serde_json::from_str::<cgminer::StatsResp>(&resp)?)?;
+        Ok(Tuning {
+            global: TuningConfig {
+                // Avalon only reports the running frequency, not the target
+                frequency: Some(estats.freq),
+                frequency_offset: None,
+                voltage: Some(estats.ps.set_volt_hash as f64),
+            },
+            boards: (0..estats.sys_status.nboards as usize).map(|i| BoardTuning {
+                index: i,
+                target: None,
+                frequency: Some(estats.freq),
+            }).collect(),
+        })
+    }
+
+    async fn set_tuning(&mut self, board: Option<usize>, tuning: TuningConfig) -> Result<(), Error> {
+        // Voltage is only exposed as an opaque level
+        if tuning.voltage.is_some() || tuning.frequency_offset.is_some() {
+            return Err(Error::NotSupported);
+        }
+        let freq = match tuning.frequency {
+            Some(freq) => freq.round() as u32,
+            None => return Ok(()),
+        };
+        // Every PLL gets the same frequency, optionally narrowed to module 0 and a single board
+        let mut param = format!("0,frequency,{0}:{0}:{0}:{0}", freq);
+        if let Some(board) = board {
+            param.push_str(&format!("-0-{}", board));
+        }
+        let cmd = json!({
+            "command": "ascset",
+            "parameter": param,
+        });
+        let resp = self.client.send_recv(&self.ip, self.port, &cmd).await?;
+        let status = serde_json::from_str::<cgminer::StatusResp>(&resp)?;
+        match status.status[0].status {
+            cgminer::StatusCode::SUCC | cgminer::StatusCode::INFO => Ok(()),
+            _ => Err(Error::ApiCallFailed(status.status[0].msg.clone())),
+        }
+    }
 }
@@ -0,0 +1,30 @@
+use serde::Deserialize;
+
+use super::{StatsResp, VersionResp};
+
+/// Response to a joined command, cgminer nests each reply under the command name
+#[derive(Deserialize, Debug)]
+pub struct SnapshotResp {
+    pub version: [VersionResp; 1],
+    pub estats: [StatsResp; 1],
+}
+
+impl SnapshotResp {
+    pub fn get_cmd() -> &'static str {
+        r#"{"command":"version+estats"}"#
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_parses() {
+        let s = r#"{"version":[{"STATUS":[{"STATUS":"S","When":11849,"Code":22,"Msg":"CGMiner versions","Description":"cgminer 4.11.1"}],"VERSION":[{"CGMiner":"4.11.1","API":"3.7","STM8":"20.08.01","PROD":"AvalonMiner 1246-81","MODEL":"1246-81","HWTYPE":"MM3v2_X3","SWTYPE":"MM314","VERSION":"21030201_4ec6bb0_09b1765","LOADER":"d0d779de.00","DNA":"020100000828a153","MAC":"b4a2eb3460fa","UPAPI":"2"}],"id":1}],"estats":[{"STATUS":[{"STATUS":"S","When":1849,"Code":70,"Msg":"CGMiner stats","Description":"cgminer 4.11.1"}],"STATS":[{"STATS":0,"ID":"AVA100","Elapsed":1750,"Calls":0,"Wait":0.000000,"Max":0.000000,"Min":99999999.000000,"MM ID0":"Ver[1246-81-21030201_4ec6bb0_09b1765]"}],"id":1}],"id":1}"#;
+        let resp: SnapshotResp = serde_json::from_str(s).unwrap();
+        let version = resp.version[0].version.as_ref().unwrap();
+        assert_eq!(version[0].model().unwrap(), "1246");
+        assert!(resp.estats[0].stats.is_some());
+    }
+}
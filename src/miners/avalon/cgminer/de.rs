@@ -697,11 +697,12 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut MsgDeserializer<'de> {
         unimplemented!()
     }
 
-    fn deserialize_option<V>(self, _visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>
     {
-        unimplemented!()
+        // Absent keys never reach us, so anything we're asked for is present
+        visitor.visit_some(self)
     }
 
     fn deserialize_unit<V>(self, _visitor: V) -> Result<V::Value, Self::Error>
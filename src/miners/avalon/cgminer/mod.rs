@@ -5,6 +5,8 @@ mod estats;
 pub use estats::*;
 mod version;
 pub use version::*;
+mod bulk;
+pub use bulk::*;
 
 pub use de::Error;
 pub use crate::common::{
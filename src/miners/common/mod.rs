@@ -15,10 +15,17 @@ pub use asc::*;
 use serde::Deserialize;
 
 // We ship a bulk command for as much info as possible
+// Backends talking to cgminer forks can swap in their own section types
 #[derive(Deserialize, Debug)]
-pub struct BulkResponse {
-    pub summary: [SummaryResp; 1],
-    pub pools: [PoolsResp; 1],
-    pub devs: [DevsResp; 1],
-    pub stats: [StatsResp; 1],
+pub struct BulkResponse<S = SummaryResp, P = PoolsResp, D = DevsResp, T = StatsResp> {
+    pub summary: [S; 1],
+    pub pools: [P; 1],
+    pub devs: [D; 1],
+    pub stats: [T; 1],
+}
+
+impl<S, P, D, T> BulkResponse<S, P, D, T> {
+    pub fn get_cmd() -> &'static str {
+        r#"{"command":"summary+pools+devs+stats"}"#
+    }
 }
\ No newline at end of file
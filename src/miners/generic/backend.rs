@@ -0,0 +1,314 @@
+use async_trait::async_trait;
+use serde_json::json;
+use std::time::Duration;
+
+use crate::miner::{share_time_from, validate_pools, Capability, Hashboard, Miner, MinerFault, MinerSnapshot, Pool, PoolStats, PoolStatus, ShareStats};
+use crate::miners::common::{self, Status, StatusCode};
+use crate::miners::generic::cgminer;
+use crate::error::Error;
+use crate::units::{Efficiency, Hashrate, Power, Temperature};
+use crate::Client;
+
+/// Fallback for anything answering the cgminer socket API that detection doesn't recognise
+/// Only the commands every fork shares are used, so most vendor features are NotSupported
+pub struct GenericCgminer {
+    ip: String,
+    port: u16,
+    client: Client,
+}
+
+impl GenericCgminer {
+    fn check_status(status: &[Status], cmd: &str) -> Result<(), Error> {
+        match status.first() {
+            Some(s) if s.status == StatusCode::SUCC || s.status == StatusCode::INFO => Ok(()),
+            Some(s) => Err(Error::ApiCallFailed(s.msg.clone())),
+            None => Err(Error::ApiCallFailed(cmd.to_string())),
+        }
+    }
+
+    async fn get_summary(&self) -> Result<(cgminer::Summary, Option<String>), Error> {
+        let resp = self.client.send_recv(&self.ip, self.port, &json!({"command": "summary"})).await?;
+        Self::summary_from(serde_json::from_str(&resp)?)
+    }
+
+    /// The summary and the miner software, which cgminer puts in the status description
+    fn summary_from(summary: cgminer::SummaryResp) -> Result<(cgminer::Summary, Option<String>), Error> {
+        Self::check_status(&summary.status, "summary")?;
+        let description = summary.status.into_iter().next().and_then(|s| s.description);
+        match summary.summary.and_then(|s| s.into_iter().next()) {
+            Some(s) => Ok((s, description)),
+            None => Err(Error::ApiCallFailed("summary".to_string())),
+        }
+    }
+
+    async fn get_pool_descs(&self) -> Result<Vec<cgminer::PoolDesc>, Error> {
+        let resp = self.client.send_recv(&self.ip, self.port, &json!({"command": "pools"})).await?;
+        Self::pools_from(serde_json::from_str(&resp)?)
+    }
+
+    fn pools_from(pools: cgminer::PoolsResp) -> Result<Vec<cgminer::PoolDesc>, Error> {
+        Self::check_status(&pools.status, "pools")?;
+        Ok(pools.pools.unwrap_or_default())
+    }
+
+    async fn get_devs(&self) -> Result<Vec<cgminer::Device>, Error> {
+        let resp = self.client.send_recv(&self.ip, self.port, &json!({"command": "devs"})).await?;
+        Self::devs_from(serde_json::from_str(&resp)?)
+    }
+
+    fn devs_from(devs: cgminer::DevsResp) -> Result<Vec<cgminer::Device>, Error> {
+        Self::check_status(&devs.status, "devs")?;
+        Ok(devs.devs.unwrap_or_default())
+    }
+
+    /// Hottest device, plenty of drivers don't report one
+    fn temperature_from(devs: &[cgminer::Device]) -> Option<Temperature> {
+        devs.iter()
+            .filter_map(|dev| dev.temperature)
+            .reduce(f64::max)
+            .map(Temperature::from_celsius)
+    }
+
+    /// Write commands need the host to grant privileged API access, which most don't by default
+    async fn command(&self, command: &str, parameter: &str) -> Result<(), Error> {
+        let cmd = json!({
+            "command": command,
+            "parameter": parameter,
+        });
+        let resp = self.client.send_recv(&self.ip, self.port, &cmd).await?;
+        let status: common::StatusResp = serde_json::from_str(&resp)?;
+        Self::check_status(&status.status, command)
+    }
+
+    fn find_pool(descs: &[cgminer::PoolDesc], pool: &Pool) -> Option<usize> {
+        descs.iter()
+            .find(|d| d.user == pool.username && d.url.parse().ok().as_ref() == Some(&pool.url))
+            .map(|d| d.pool)
+    }
+}
+
+#[async_trait]
+impl Miner for GenericCgminer {
+    fn new(client: Client, ip: String, port: u16) -> Self {
+        GenericCgminer {
+            ip,
+            port,
+            client,
+        }
+    }
+
+    fn get_type(&self) -> &'static str {
+        "GenericCgminer"
+    }
+
+    fn capabilities(&self) -> &'static [Capability] {
+        &[
+            Capability::ReadPools,
+            Capability::WritePools,
+            Capability::PoolStats,
+            Capability::ShareStats,
+            Capability::FirmwareVersion,
+            Capability::Uptime,
+            Capability::Hashboards,
+        ]
+    }
+
+    fn max_pools(&self) -> Option<usize> {
+        // cgminer grows its pool list as needed
+        None
+    }
+
+    async fn get_model(&self) -> Result<String, Error> {
+        // The closest thing to a model is the Type some drivers put in their stats section
+        let resp = self.client.send_recv(&self.ip, self.port, &json!({"command": "stats"})).await?;
+        let stats: cgminer::StatsResp = serde_json::from_str(&resp)?;
+        Self::check_status(&stats.status, "stats")?;
+        stats.model().ok_or(Error::NotSupported)
+    }
+
+    async fn auth(&mut self, _username: &str, _password: &str) -> Result<(), Error> {
+        // The cgminer API has no authentication, access is by IP
+        Ok(())
+    }
+
+    /// Restarts the mining process, cgminer has no way to reboot the host
+    async fn reboot(&mut self) -> Result<(), Error> {
+        // cgminer replies with a bare RESTART status before going down, there's nothing to check
+        self.client.send(&self.ip, self.port, &json!({"command": "restart"})).await
+    }
+
+    async fn get_hashrate(&self) -> Result<Hashrate, Error> {
+        let (summary, _) = self.get_summary().await?;
+        summary.hashrate().ok_or(Error::InvalidResponse)
+    }
+
+    async fn get_power(&self) -> Result<Power, Error> {
+        Err(Error::NotSupported)
+    }
+
+    async fn get_efficiency(&self) -> Result<Efficiency, Error> {
+        Err(Error::NotSupported)
+    }
+
+    async fn get_nameplate_rate(&self) -> Result<Hashrate, Error> {
+        Err(Error::NotSupported)
+    }
+
+    async fn get_temperature(&self) -> Result<Temperature, Error> {
+        Self::temperature_from(&self.get_devs().await?).ok_or(Error::NotSupported)
+    }
+
+    async fn get_fan_speed(&self) -> Result<Vec<u32>, Error> {
+        Err(Error::NotSupported)
+    }
+
+    async fn get_pools(&self) -> Result<Vec<Pool>, Error> {
+        Ok(self.get_pool_descs().await?.iter()
+            .filter_map(|p| Pool::from_slot(&p.url, &p.user, None))
+            .collect())
+    }
+
+    /// Adds any pools cgminer doesn't have, switches to the first and removes the rest
+    /// Nothing is saved across a restart
+    async fn set_pools(&mut self, pools: Vec<Pool>) -> Result<(), Error> {
+        validate_pools(&pools, self.max_pools())?;
+        // addpool takes comma separated fields with no escaping
+        for pool in pools.iter() {
+            if pool.username.contains(',') || pool.password.as_deref().unwrap_or_default().contains(',') {
+                return Err(Error::InvalidArgument(format!("Pool {} credentials can't contain commas", pool.url)));
+            }
+        }
+        let existing = self.get_pool_descs().await?;
+        for pool in pools.iter().filter(|pool| Self::find_pool(&existing, pool).is_none()) {
+            // cgminer won't take an empty password, "x" is the usual placeholder
+            let param = format!("{},{},{}", pool.url, pool.username, pool.password.as_deref().unwrap_or("x"));
+            self.command("addpool", &param).await?;
+        }
+        let descs = self.get_pool_descs().await?;
+        let first = Self::find_pool(&descs, &pools[0]).ok_or(Error::ApiCallFailed("addpool".to_string()))?;
+        self.command("switchpool", &first.to_string()).await?;
+        // cgminer renumbers the pools after each removal, so work down from the highest
+        let keep = pools.iter().filter_map(|pool| Self::find_pool(&descs, pool)).collect::<Vec<_>>();
+        let mut stale = descs.iter().map(|d| d.pool).filter(|id| !keep.contains(id)).collect::<Vec<_>>();
+        stale.sort_unstable_by(|a, b| b.cmp(a));
+        for id in stale {
+            self.command("removepool", &id.to_string()).await?;
+        }
+        Ok(())
+    }
+
+    async fn get_pool_stats(&self) -> Result<Vec<PoolStats>, Error> {
+        Ok(self.get_pool_descs().await?.iter().map(|p| PoolStats {
+            url: p.url.clone(),
+            username: p.user.clone(),
+            status: PoolStatus::from_cgminer(&p.status),
+            active: p.stratum_active,
+            priority: p.priority,
+            accepted: p.accepted,
+            rejected: p.rejected,
+            stale: p.stale,
+            difficulty_accepted: p.difficulty_accepted.unwrap_or_default(),
+            last_share_time: p.last_share_time.and_then(|t| share_time_from(t as usize)),
+            stratum_difficulty: p.stratum_difficulty,
+        }).collect())
+    }
+
+    async fn get_share_stats(&self) -> Result<ShareStats, Error> {
+        let (summary, _) = self.get_summary().await?;
+        Ok(ShareStats {
+            accepted: summary.accepted,
+            rejected: summary.rejected,
+            stale: summary.stale,
+            discarded: summary.discarded,
+            hardware_errors: summary.hardware_errors,
+            best_share: summary.best_share,
+            difficulty_accepted: summary.difficulty_accepted,
+            difficulty_rejected: summary.difficulty_rejected,
+            pool_rejected_percent: summary.pool_rejected_per,
+            pool_stale_percent: summary.pool_stale_per,
+            hardware_error_percent: summary.device_hardware_per,
+        })
+    }
+
+    async fn get_sleep(&self) -> Result<bool, Error> {
+        Err(Error::NotSupported)
+    }
+
+    async fn set_sleep(&mut self, _sleep: bool) -> Result<(), Error> {
+        Err(Error::NotSupported)
+    }
+
+    async fn get_blink(&self) -> Result<bool, Error> {
+        Err(Error::NotSupported)
+    }
+
+    async fn set_blink(&mut self, _blink: bool) -> Result<(), Error> {
+        Err(Error::NotSupported)
+    }
+
+    async fn get_logs(&mut self) -> Result<Vec<String>, Error> {
+        Err(Error::NotSupported)
+    }
+
+    async fn get_mac(&self) -> Result<String, Error> {
+        Err(Error::NotSupported)
+    }
+
+    async fn get_firmware_version(&self) -> Result<String, Error> {
+        // Every response's status carries the miner software and version, e.g. "cgminer 4.11.1"
+        let (_, description) = self.get_summary().await?;
+        description.ok_or(Error::NotSupported)
+    }
+
+    async fn get_uptime(&self) -> Result<Duration, Error> {
+        let (summary, _) = self.get_summary().await?;
+        summary.elapsed.map(Duration::from_secs).ok_or(Error::NotSupported)
+    }
+
+    async fn get_errors(&mut self) -> Result<Vec<MinerFault>, Error> {
+        Err(Error::NotSupported)
+    }
+
+    async fn get_snapshot(&self) -> Result<MinerSnapshot, Error> {
+        // cgminer joins commands, so this is a single round-trip
+        let resp = self.client.send_recv(&self.ip, self.port, &cgminer::BulkResp::get_cmd()).await?;
+        let bulk = serde_json::from_str::<cgminer::BulkResp>(&resp)?;
+        let [summary] = bulk.summary;
+        let [pools] = bulk.pools;
+        let [devs] = bulk.devs;
+        let [stats] = bulk.stats;
+        let (summary, _) = Self::summary_from(summary)?;
+        Ok(MinerSnapshot {
+            model: stats.model().unwrap_or_else(|| self.get_type().to_string()),
+            // cgminer doesn't know the MAC, power or rating of the hardware it drives
+            mac: String::new(),
+            hashrate: summary.hashrate().ok_or(Error::InvalidResponse)?,
+            power: None,
+            power_estimated: false,
+            efficiency: None,
+            nameplate_rate: Hashrate::default(),
+            temperature: Self::temperature_from(&Self::devs_from(devs)?).unwrap_or_default(),
+            fan_speed: vec![],
+            pools: Some(Self::pools_from(pools)?.iter()
+                .filter_map(|p| Pool::from_slot(&p.url, &p.user, None))
+                .collect()),
+            sleep: None,
+        })
+    }
+
+    async fn get_hashboards(&self) -> Result<Vec<Hashboard>, Error> {
+        // Each cgminer device is usually a hashboard or chain
+        Ok(self.get_devs().await?.iter().enumerate().map(|(i, dev)| Hashboard {
+            index: i,
+            online: dev.online(),
+            hashrate: dev.hashrate(),
+            ideal_rate: None,
+            chip_count: None,
+            chip_temp: dev.temperature.map(Temperature::from_celsius),
+            pcb_temp: None,
+            frequency: None,
+            hw_errors: dev.hardware_errors,
+            serial: None,
+        }).collect())
+    }
+}
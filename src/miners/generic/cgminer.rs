@@ -0,0 +1,224 @@
+use serde::{Deserialize, Deserializer};
+
+use crate::miners::common::{BulkResponse, Status};
+use crate::units::Hashrate;
+
+// Forks of cgminer (bmminer, sgminer, bfgminer...) drop, rename and retype fields freely,
+// so everything here is optional and only what the generic backend reads is parsed
+
+/// Numbers that some forks send as strings, "13,512.20" or "0:00:12" style values we can't read become None
+fn deserialize_number<'de, D>(deserializer: D) -> Result<Option<f64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Number {
+        F64(f64),
+        Str(String),
+    }
+    Ok(match Option::<Number>::deserialize(deserializer)? {
+        Some(Number::F64(n)) => Some(n),
+        Some(Number::Str(s)) => s.trim().replace(',', "").parse().ok(),
+        None => None,
+    })
+}
+
+/// Prefer the 5s average, forks report in GH/s where cgminer uses MH/s
+fn hashrate_from(mhs_5s: Option<f64>, ghs_5s: Option<f64>, mhs_av: Option<f64>, ghs_av: Option<f64>) -> Option<Hashrate> {
+    mhs_5s.map(Hashrate::from_mhs)
+        .or(ghs_5s.map(Hashrate::from_ghs))
+        .or(mhs_av.map(Hashrate::from_mhs))
+        .or(ghs_av.map(Hashrate::from_ghs))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Summary {
+    #[serde(rename = "Elapsed")]
+    pub elapsed: Option<u64>,
+    #[serde(rename = "MHS 5s", default, deserialize_with = "deserialize_number")]
+    pub mhs_5s: Option<f64>,
+    #[serde(rename = "GHS 5s", default, deserialize_with = "deserialize_number")]
+    pub ghs_5s: Option<f64>,
+    #[serde(rename = "MHS av", default, deserialize_with = "deserialize_number")]
+    pub mhs_av: Option<f64>,
+    #[serde(rename = "GHS av", default, deserialize_with = "deserialize_number")]
+    pub ghs_av: Option<f64>,
+    #[serde(rename = "Accepted", default)]
+    pub accepted: usize,
+    #[serde(rename = "Rejected", default)]
+    pub rejected: usize,
+    #[serde(rename = "Stale")]
+    pub stale: Option<usize>,
+    #[serde(rename = "Discarded")]
+    pub discarded: Option<usize>,
+    #[serde(rename = "Hardware Errors")]
+    pub hardware_errors: Option<usize>,
+    #[serde(rename = "Best Share")]
+    pub best_share: Option<usize>,
+    #[serde(rename = "Difficulty Accepted", default, deserialize_with = "deserialize_number")]
+    pub difficulty_accepted: Option<f64>,
+    #[serde(rename = "Difficulty Rejected", default, deserialize_with = "deserialize_number")]
+    pub difficulty_rejected: Option<f64>,
+    #[serde(rename = "Device Hardware%", default, deserialize_with = "deserialize_number")]
+    pub device_hardware_per: Option<f64>,
+    #[serde(rename = "Pool Rejected%", default, deserialize_with = "deserialize_number")]
+    pub pool_rejected_per: Option<f64>,
+    #[serde(rename = "Pool Stale%", default, deserialize_with = "deserialize_number")]
+    pub pool_stale_per: Option<f64>,
+}
+
+impl Summary {
+    pub fn hashrate(&self) -> Option<Hashrate> {
+        hashrate_from(self.mhs_5s, self.ghs_5s, self.mhs_av, self.ghs_av)
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SummaryResp {
+    #[serde(rename = "STATUS")]
+    pub status: Vec<Status>,
+    #[serde(rename = "SUMMARY")]
+    pub summary: Option<Vec<Summary>>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PoolDesc {
+    #[serde(rename = "POOL")]
+    pub pool: usize,
+    #[serde(rename = "URL")]
+    pub url: String,
+    #[serde(rename = "Status")]
+    pub status: String,
+    #[serde(rename = "Priority")]
+    pub priority: usize,
+    #[serde(rename = "User", default)]
+    pub user: String,
+    #[serde(rename = "Accepted", default)]
+    pub accepted: usize,
+    #[serde(rename = "Rejected", default)]
+    pub rejected: usize,
+    #[serde(rename = "Stale", default)]
+    pub stale: usize,
+    #[serde(rename = "Difficulty Accepted", default, deserialize_with = "deserialize_number")]
+    pub difficulty_accepted: Option<f64>,
+    #[serde(rename = "Last Share Time", default, deserialize_with = "deserialize_number")]
+    pub last_share_time: Option<f64>,
+    #[serde(rename = "Stratum Active", default)]
+    pub stratum_active: bool,
+    #[serde(rename = "Stratum Difficulty", default, deserialize_with = "deserialize_number")]
+    pub stratum_difficulty: Option<f64>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PoolsResp {
+    #[serde(rename = "STATUS")]
+    pub status: Vec<Status>,
+    #[serde(rename = "POOLS")]
+    pub pools: Option<Vec<PoolDesc>>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Device {
+    #[serde(rename = "Enabled")]
+    pub enabled: Option<String>,
+    #[serde(rename = "Status")]
+    pub status: Option<String>,
+    #[serde(rename = "Temperature", default, deserialize_with = "deserialize_number")]
+    pub temperature: Option<f64>,
+    #[serde(rename = "MHS 5s", default, deserialize_with = "deserialize_number")]
+    pub mhs_5s: Option<f64>,
+    #[serde(rename = "GHS 5s", default, deserialize_with = "deserialize_number")]
+    pub ghs_5s: Option<f64>,
+    #[serde(rename = "MHS av", default, deserialize_with = "deserialize_number")]
+    pub mhs_av: Option<f64>,
+    #[serde(rename = "GHS av", default, deserialize_with = "deserialize_number")]
+    pub ghs_av: Option<f64>,
+    #[serde(rename = "Hardware Errors")]
+    pub hardware_errors: Option<usize>,
+}
+
+impl Device {
+    pub fn hashrate(&self) -> Option<Hashrate> {
+        hashrate_from(self.mhs_5s, self.ghs_5s, self.mhs_av, self.ghs_av)
+    }
+
+    /// Enabled and alive, devices that don't say are assumed to be
+    pub fn online(&self) -> bool {
+        self.enabled.as_deref().map_or(true, |e| e == "Y")
+            && self.status.as_deref().map_or(true, |s| s.eq_ignore_ascii_case("alive"))
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct DevsResp {
+    #[serde(rename = "STATUS")]
+    pub status: Vec<Status>,
+    #[serde(rename = "DEVS")]
+    pub devs: Option<Vec<Device>>,
+}
+
+/// Only the Type some drivers include is read, the rest of a stats section varies by driver
+#[derive(Deserialize, Debug)]
+pub struct StatSection {
+    #[serde(rename = "Type")]
+    pub type_: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct StatsResp {
+    #[serde(rename = "STATUS")]
+    pub status: Vec<Status>,
+    #[serde(rename = "STATS")]
+    pub stats: Option<Vec<StatSection>>,
+}
+
+impl StatsResp {
+    pub fn model(&self) -> Option<String> {
+        self.stats.as_ref()?.iter().find_map(|stat| stat.type_.clone())
+    }
+}
+
+/// summary+pools+devs+stats in one request
+pub type BulkResp = BulkResponse<SummaryResp, PoolsResp, DevsResp, StatsResp>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::from_str;
+
+    #[test]
+    fn it_parses_fork_summaries() {
+        let cgminer = r#"{"STATUS":[{"STATUS":"S","When":1700000000,"Code":11,"Msg":"Summary","Description":"cgminer 4.11.1"}],"SUMMARY":[{"Elapsed":3600,"MHS av":13500000.5,"MHS 5s":13600000.0,"Accepted":120,"Rejected":2,"Best Share":50000}],"id":1}"#;
+        let resp: SummaryResp = from_str(cgminer).unwrap();
+        let summary = &resp.summary.unwrap()[0];
+        assert_eq!(summary.hashrate(), Some(Hashrate::from_mhs(13600000.0)));
+        assert_eq!(summary.elapsed, Some(3600));
+        assert_eq!(summary.stale, None);
+
+        let bmminer = r#"{"STATUS":[{"STATUS":"S","When":1700000000,"Code":11,"Msg":"Summary","Description":"bmminer 1.0.0"}],"SUMMARY":[{"Elapsed":60,"GHS 5s":"13,512.20","GHS av":13400.1,"Accepted":3}],"id":1}"#;
+        let resp: SummaryResp = from_str(bmminer).unwrap();
+        let summary = &resp.summary.unwrap()[0];
+        assert_eq!(summary.hashrate(), Some(Hashrate::from_ghs(13512.2)));
+        assert_eq!(summary.rejected, 0);
+
+        let devs = r#"{"STATUS":[{"STATUS":"S","Msg":"1 ASC(s)"}],"DEVS":[{"ASC":0,"Enabled":"Y","Status":"Alive","Temperature":"n/a","GHS 5s":4500.0}]}"#;
+        let resp: DevsResp = from_str(devs).unwrap();
+        let dev = &resp.devs.unwrap()[0];
+        assert!(dev.online());
+        assert_eq!(dev.temperature, None);
+    }
+
+    #[test]
+    fn it_parses_bulk_responses() {
+        let bulk = r#"{"summary":[{"STATUS":[{"STATUS":"S","Msg":"Summary","Description":"sgminer 5.6.0"}],"SUMMARY":[{"Elapsed":10,"MHS 5s":1000.0}]}],
+            "pools":[{"STATUS":[{"STATUS":"S","Msg":"1 Pool(s)"}],"POOLS":[{"POOL":0,"URL":"stratum+tcp://btc.pool.com:3333","Status":"Alive","Priority":0,"User":"worker","Last Share Time":"0:00:12"}]}],
+            "devs":[{"STATUS":[{"STATUS":"S","Msg":"1 GPU(s)"}],"DEVS":[{"GPU":0,"Temperature":61.5,"MHS 5s":1000.0}]}],
+            "stats":[{"STATUS":[{"STATUS":"S","Msg":"CGMiner stats"}],"STATS":[{"STATS":0,"ID":"POOL0","Elapsed":10},{"STATS":1,"ID":"BTM0","Type":"Antminer L3+"}]}],"id":1}"#;
+        let bulk: BulkResp = from_str(bulk).unwrap();
+        assert_eq!(bulk.summary[0].summary.as_ref().unwrap()[0].elapsed, Some(10));
+        assert_eq!(bulk.pools[0].pools.as_ref().unwrap()[0].last_share_time, None);
+        assert_eq!(bulk.devs[0].devs.as_ref().unwrap()[0].temperature, Some(61.5));
+        assert_eq!(bulk.stats[0].model().as_deref(), Some("Antminer L3+"));
+    }
+}
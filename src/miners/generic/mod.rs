@@ -0,0 +1,3 @@
+mod cgminer;
+mod backend;
+pub use backend::GenericCgminer;
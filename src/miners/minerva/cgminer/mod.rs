@@ -14,6 +14,8 @@ mod led;
 pub use led::*;
 mod hashboards;
 pub use hashboards::*;
+mod work_mode;
+pub use work_mode::*;
 
 use serde::Deserialize;
 
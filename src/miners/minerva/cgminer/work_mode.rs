@@ -0,0 +1,79 @@
+use serde::{Deserialize, Serialize};
+
+use crate::miner::WorkMode;
+
+/// What workMode returns and setWorkMode takes, fields we don't touch are sent back as read
+#[derive(Serialize, Deserialize, Debug)]
+pub struct WorkModeData {
+    /// Boards switched on, "0x0" when sleeping and "0xf" with all four on
+    pub mask: String,
+    /// Performance level, "0" is low power, "1" normal and "2" high performance
+    /// Missing on firmware that only switches the boards
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub level: Option<String>,
+    #[serde(flatten)]
+    pub other: serde_json::Map<String, serde_json::Value>,
+}
+
+impl WorkModeData {
+    pub fn work_mode(&self) -> WorkMode {
+        if self.mask == "0x0" {
+            return WorkMode::Sleep;
+        }
+        match self.level.as_deref().map(|l| l.trim().parse::<u8>()) {
+            Some(Ok(0)) => WorkMode::LowPower,
+            Some(Ok(2)) => WorkMode::HighPerformance,
+            Some(Ok(n)) if n != 1 => WorkMode::Vendor(n),
+            _ => WorkMode::Normal,
+        }
+    }
+
+    /// Levels wake the boards, sleeping keeps the level for when they come back
+    pub fn set_work_mode(&mut self, mode: WorkMode) {
+        let level = match mode {
+            WorkMode::Sleep => {
+                self.mask = "0x0".to_string();
+                return;
+            },
+            WorkMode::LowPower => 0,
+            WorkMode::Normal => 1,
+            WorkMode::HighPerformance => 2,
+            WorkMode::Vendor(n) => n,
+        };
+        self.mask = "0xf".to_string();
+        self.level = Some(level.to_string());
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct WorkModeResp {
+    pub data: WorkModeData,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_maps_work_mode_levels() {
+        let resp: WorkModeResp = serde_json::from_str(r#"{"code":200,"message":"success","data":{"mask":"0xf","level":"0","freq":"600"}}"#).unwrap();
+        let mut data = resp.data;
+        assert_eq!(data.work_mode(), WorkMode::LowPower);
+        for (level, mode) in [("1", WorkMode::Normal), ("2", WorkMode::HighPerformance), ("5", WorkMode::Vendor(5))] {
+            data.level = Some(level.to_string());
+            assert_eq!(data.work_mode(), mode);
+        }
+
+        data.set_work_mode(WorkMode::Sleep);
+        assert_eq!(data.work_mode(), WorkMode::Sleep);
+        assert_eq!(data.level.as_deref(), Some("5"));
+        data.set_work_mode(WorkMode::HighPerformance);
+        assert_eq!(data.mask, "0xf");
+        assert_eq!(data.work_mode(), WorkMode::HighPerformance);
+        // Settings we don't know about go back as they came
+        assert_eq!(serde_json::to_value(&data).unwrap()["freq"], "600");
+
+        let boards_only: WorkModeData = serde_json::from_str(r#"{"mask":"0xf"}"#).unwrap();
+        assert_eq!(boards_only.work_mode(), WorkMode::Normal);
+    }
+}
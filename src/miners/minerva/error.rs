@@ -1,23 +1,31 @@
 use lazy_regex::regex;
 
-use crate::miner::MinerError;
+use crate::miner::{MinerError, FaultCategory, FaultSeverity};
 
 pub static MineraErrors: [MinerError; 4] = [
     MinerError {
         re: regex!(r"power up to.+failed read_bak"),
         msg: "PSU failed to power up",
+        category: FaultCategory::Power,
+        severity: FaultSeverity::Critical,
     },
     MinerError {
         re: regex!(r"ACK not found"),
         msg: "SPI ACK not found",
+        category: FaultCategory::Hashboard,
+        severity: FaultSeverity::Error,
     },
     MinerError {
         re: regex!("low fan speed of fan ([0-9])"),
         msg: "Fan {} speed too low",
+        category: FaultCategory::Fan,
+        severity: FaultSeverity::Warning,
     },
     MinerError {
         re: regex!("C3012 ([0-9]) failure, disabling!"),
         msg: "Chain {} failure",
+        category: FaultCategory::Hashboard,
+        severity: FaultSeverity::Error,
     },
 ];
 
@@ -25,33 +33,49 @@ pub static MinerVaErrors: [MinerError; 8] = [
     MinerError {
         re: regex!(r".+Error: fan ([0-9]) failed"),
         msg: "Fan {} failed",
+        category: FaultCategory::Fan,
+        severity: FaultSeverity::Error,
     },
     MinerError {
         re: regex!(r".+booting board ([0-9]).+\n.+ACK not found"),
         msg: "Board {} ACK not found",
+        category: FaultCategory::Hashboard,
+        severity: FaultSeverity::Error,
     },
     MinerError {
         re: regex!(r".+(voltage not up to standard|电源故障，电压不达标)"),
         msg: "Voltage not up to standard",
+        category: FaultCategory::Power,
+        severity: FaultSeverity::Error,
     },
     MinerError {
         re: regex!(r".+Error: init power supply"),
         msg: "Unable to init power supply",
+        category: FaultCategory::Power,
+        severity: FaultSeverity::Critical,
     },
     MinerError {
         re: regex!(r".+(?:init chip|启动芯片|初始化芯片)([0-9])/([0-9])"),
         msg: "Failed to init board {} chip {}",
+        category: FaultCategory::Chip,
+        severity: FaultSeverity::Error,
     },
     MinerError {
         re: regex!(r".+mv64xxx_i2c_fsm: Ctlr Error"),
         msg: "I2C controller error",
+        category: FaultCategory::ControlBoard,
+        severity: FaultSeverity::Error,
     },
     MinerError {
         re: regex!(r".+Stratum connection to pool [0-9] interrupted.+\n.+flushing server.+\n.+flush failed"),
         msg: "Connection interrupted, failed to flush server",
+        category: FaultCategory::Network,
+        severity: FaultSeverity::Warning,
     },
     MinerError {
         re: regex!(r".+read eeprom failed:I2C(Nix(ENXIO))"),
         msg: "Failed to read EEPROM, device did not respond",
+        category: FaultCategory::Eeprom,
+        severity: FaultSeverity::Error,
     }
 ];
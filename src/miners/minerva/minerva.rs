@@ -3,14 +3,17 @@ use std::collections::HashMap;
 use async_trait::async_trait;
 use reqwest::multipart::Form;
 use serde_json::json;
-use tracing::{warn, error};
+use tracing::warn;
 use std::collections::HashSet;
+use std::time::Duration;
 use scraper::{Html, Selector};
 
 use crate::Client;
-use crate::miner::{Miner, Pool};
+use crate::miner::{check_mac_confirmation, pool_slots, share_time_from, validate_pools, Capability, Hashboard, LogEntry, Miner, MinerFault, MinerSnapshot, NetworkConfig, Pool, PoolStats, PoolStatus, ShareStats, UpgradeStage, WorkMode, UPGRADE_TIMEOUT};
+use crate::util::{log, multipart, upload};
 use crate::miners::{minerva, common};
 use crate::error::Error;
+use crate::units::{Efficiency, Hashrate, Power, Temperature};
 use minerva::{cgminer, minera};
 use minerva::error::{MinerVaErrors, MineraErrors};
 
@@ -22,34 +25,59 @@ pub struct Minera {
     client: Client,
 }
 
+/// Minerva doesn't report a nameplate rate, so we have to guess
+/// There are 3 models with hashrates varying from 75 to 105 TH/s
+/// Assume the middle of the road 90 TH/s
+/// Most models are 3 board models, so assume 90/3 = 30 TH/s per board
+/// Unless we detect 4 boards in which case assume 90/4 = 22.5 TH/s per board
+///
+/// Edge case: This will be incorrect for 4 board models with at least 1 board disconnected
+fn nameplate_from_boards(boards: u8) -> Hashrate {
+    Hashrate::from_ths(boards as f64 * if boards == 4 { 22.5 } else { 30.0 })
+}
+
 impl Minera {
-    /// Returns the number of hashboards detected and the number online
-    async fn get_board_count(&self) -> Result<u8, Error> {
+    async fn get_stats(&self) -> Result<minera::StatsResp, Error> {
         let resp = self.client.http_client
-            .get(&format!("http://{}/index.php/app/stats", self.ip))
+            .get(format!("http://{}/index.php/app/stats", self.ip))
             .send()
             .await?;
         if resp.status().is_success() {
-            let stat: minera::StatsResp = resp.json().await?;
-            if let minera::StatsResp::Running(stat) = stat {
-                if stat.devices.board_4.is_some() {
-                    Ok(4)
-                } else if stat.devices.board_3.is_some() {
-                    Ok(3)
-                } else if stat.devices.board_2.is_some() {
-                    Ok(2)
-                } else if stat.devices.board_1.is_some() {
-                    Ok(1)
-                } else {
-                    Ok(0)
-                }
-            } else {
-                Err(Error::InvalidResponse)
-            }
+            Ok(resp.json().await?)
         } else {
             Err(Error::HttpRequestFailed)
         }
     }
+
+    /// Returns the number of hashboards detected
+    fn board_count_from(stat: &minera::RunningStats) -> u8 {
+        if stat.devices.board_4.is_some() {
+            4
+        } else if stat.devices.board_3.is_some() {
+            3
+        } else if stat.devices.board_2.is_some() {
+            2
+        } else if stat.devices.board_1.is_some() {
+            1
+        } else {
+            0
+        }
+    }
+
+    /// sysuptime is the first field of /proc/uptime, seconds with a fractional part
+    fn uptime_from(sysuptime: &str) -> Result<Duration, Error> {
+        let secs = sysuptime.trim().parse::<f64>().map_err(|_| Error::InvalidResponse)?;
+        Duration::try_from_secs_f64(secs).map_err(|_| Error::InvalidResponse)
+    }
+
+    /// Returns the number of hashboards detected and the number online
+    async fn get_board_count(&self) -> Result<u8, Error> {
+        if let minera::StatsResp::Running(stat) = self.get_stats().await? {
+            Ok(Self::board_count_from(&stat))
+        } else {
+            Err(Error::InvalidResponse)
+        }
+    }
 }
 
 #[async_trait]
@@ -67,6 +95,27 @@ impl Miner for Minera {
         "MinerVa"
     }
 
+    fn capabilities(&self) -> &'static [Capability] {
+        &[
+            Capability::ReadPools,
+            Capability::WritePools,
+            Capability::PoolStats,
+            Capability::ShareStats,
+            Capability::Uptime,
+            Capability::Logs,
+            Capability::Errors,
+            Capability::EstimatedPower,
+            Capability::Hashboards,
+            Capability::ReadNetwork,
+            Capability::SetPassword,
+        ]
+    }
+
+    fn max_pools(&self) -> Option<usize> {
+        // Minera takes as many pools as the form is given
+        None
+    }
+
     async fn get_model(&self) -> Result<String, Error> {
         //The below doesn't respond when the miner is not running
         // let resp = self.client.send_recv(&self.ip, self.port, &json!({"command":"devdetails"})).await?;
@@ -79,7 +128,7 @@ impl Miner for Minera {
         let mut form = HashMap::new();
         form.insert("password", password);
         let resp = self.client.http_client
-            .post(&format!("http://{}/index.php/app/login", self.ip))
+            .post(format!("http://{}/index.php/app/login", self.ip))
             .form(&form)
             .send()
             .await?;
@@ -90,11 +139,12 @@ impl Miner for Minera {
         }
     }
 
-    async fn reboot(&mut self) -> Result<(), Error> {
-        //TODO: This always times out as the API reboots before responding
+    async fn set_password(&mut self, old: &str, new: &str) -> Result<(), Error> {
+        // Log in with the old password first so the settings page accepts us
+        self.auth("", old).await?;
         let resp = self.client.http_client
-            .post(&format!("http://{}/index.php/app/reboot", self.ip))
-            .query(&[("confirm", "1")])
+            .post(format!("http://{}/index.php/app/settings", self.ip))
+            .form(&[("password", new), ("password2", new), ("save_password", "1")])
             .send()
             .await?;
         if resp.status().is_success() {
@@ -104,68 +154,54 @@ impl Miner for Minera {
         }
     }
 
-    async fn get_hashrate(&self) -> Result<f64, Error> {
+    async fn reboot(&mut self) -> Result<(), Error> {
+        //TODO: This always times out as the API reboots before responding
         let resp = self.client.http_client
-            .get(&format!("http://{}/index.php/app/stats", self.ip))
+            .post(format!("http://{}/index.php/app/reboot", self.ip))
+            .query(&[("confirm", "1")])
             .send()
             .await?;
         if resp.status().is_success() {
-            let stat: minera::StatsResp = resp.json().await?;
-            if let minera::StatsResp::Running(stat) = stat {
-                // Convert to TH/S
-                Ok((stat.totals.hashrate as f64) / 1000000000000.0)
-            } else {
-                Ok(0.0)
-            }
+            Ok(())
         } else {
             Err(Error::HttpRequestFailed)
         }
     }
 
-    async fn get_power(&self) -> Result<f64, Error> {
+    async fn get_hashrate(&self) -> Result<Hashrate, Error> {
+        if let minera::StatsResp::Running(stat) = self.get_stats().await? {
+            Ok(Hashrate::from_hs(stat.totals.hashrate as f64))
+        } else {
+            Ok(Hashrate::default())
+        }
+    }
+
+    async fn get_power(&self) -> Result<Power, Error> {
         // Guess at power consumption
         // There are 3 models with efficiencies ranging from 31 - 39 J/TH
         // Assume the middle of the road 35 J/TH
-        Ok(self.get_hashrate().await? * 35.0)
+        Ok(self.get_hashrate().await? * Efficiency::from_j_per_th(35.0))
     }
 
-    async fn get_efficiency(&self) -> Result<f64, Error> {
-        Ok(35.0)
+    async fn get_efficiency(&self) -> Result<Efficiency, Error> {
+        Ok(Efficiency::from_j_per_th(35.0))
     }
 
-    async fn get_nameplate_rate(&self) -> Result<f64, Error> {
-        // Minerva doesn't report a nameplate rate, so we have to guess
-        // There are 3 models with hashrates varying from 75 to 105 TH/s
-        // Assume the middle of the road 90 TH/s
-        // Most models are 3 board models, so assume 90/3 = 30 TH/s per board
-        // Unless we detect 4 boards in which case assume 90/4 = 22.5 TH/s per board
-
-        // Edge case: This will be incorrect for 4 board models with at least 1 board disconnected
-        let boards = self.get_board_count().await?;
-        Ok(boards as f64 * if boards == 4 { 22.5 } else { 30.0 })
+    async fn get_nameplate_rate(&self) -> Result<Hashrate, Error> {
+        Ok(nameplate_from_boards(self.get_board_count().await?))
     }
 
-    async fn get_temperature(&self) -> Result<f64, Error> {
-        let resp = self.client.http_client
-            .get(&format!("http://{}/index.php/app/stats", self.ip))
-            .send()
-            .await?;
-        if resp.status().is_success() {
-            let stat = resp.json::<minera::StatsResp>().await?;
-            if let minera::StatsResp::Running(stat) = stat {
-                // Convert to TH/S
-                Ok(stat.temp)
-            } else {
-                Ok(0.0)
-            }
+    async fn get_temperature(&self) -> Result<Temperature, Error> {
+        if let minera::StatsResp::Running(stat) = self.get_stats().await? {
+            Ok(Temperature::from_celsius(stat.temp))
         } else {
-            Err(Error::HttpRequestFailed)
+            Ok(Temperature::default())
         }
     }
 
     async fn get_fan_speed(&self) -> Result<Vec<u32>, Error> {
         // let resp = self.client.http_client
-        //     .get(&format!("http://{}/index.php/app/api", self.ip))
+        //     .get(format!("http://{}/index.php/app/api", self.ip))
         //     .query(&[("command", "miner_stats")])
         //     .send()
         //     .await?;
@@ -183,7 +219,7 @@ impl Miner for Minera {
         /*
         // This implementation doesn't work when the miner is not running
         let resp = self.client.http_client
-            .get(&format!("http://{}/index.php/app/stats", self.ip))
+            .get(format!("http://{}/index.php/app/stats", self.ip))
             .send()
             .await?;
         if resp.status().is_success() {
@@ -209,7 +245,7 @@ impl Miner for Minera {
         let pool_user_selector = Selector::parse(r#"input[name="pool_username[]"]"#).unwrap();
         let pool_pass_selector = Selector::parse(r#"input[name="pool_password[]"]"#).unwrap();
         let resp = self.client.http_client
-            .get(&format!("http://{}/index.php/app/settings", self.ip))
+            .get(format!("http://{}/index.php/app/settings", self.ip))
             .send()
             .await?;
         let document = Html::parse_document(resp.text().await?.as_str());
@@ -219,11 +255,9 @@ impl Miner for Minera {
                 let url = pool.select(&pool_url_selector).next().unwrap().value().attr("value").unwrap().to_string();
                 let user = pool.select(&pool_user_selector).next().unwrap().value().attr("value").unwrap().to_string();
                 let pass = pool.select(&pool_pass_selector).next().unwrap().value().attr("value").unwrap().to_string();
-                pool_list.push(Pool {
-                    url,
-                    username: user,
-                    password: if pass.is_empty() {None} else {Some(pass)},
-                });
+                if let Some(pool) = Pool::from_slot(&url, &user, if pass.is_empty() {None} else {Some(&pass)}) {
+                    pool_list.push(pool);
+                }
             }
             Ok(pool_list)
         } else {
@@ -232,12 +266,13 @@ impl Miner for Minera {
     }
 
     async fn set_pools(&mut self, pools: Vec<Pool>) -> Result<(), Error> {
+        validate_pools(&pools, self.max_pools())?;
         let mut form = Form::new()
             .text("save_miner_pools", "1");
         
         for pool in pools {
             form = form
-                .text("pool_url[]", pool.url.clone())
+                .text("pool_url[]", pool.url.to_string())
                 .text("pool_username[]", pool.username.clone())
                 .text("pool_password[]", if let Some(ref password) = pool.password {
                     password.clone()
@@ -247,7 +282,7 @@ impl Miner for Minera {
         }
 
         let resp = self.client.http_client
-            .post(&format!("http://{}/index.php/app/settings", self.ip))
+            .post(format!("http://{}/index.php/app/settings", self.ip))
             .multipart(form)
             .send()
             .await?;
@@ -258,6 +293,45 @@ impl Miner for Minera {
         }
     }
 
+    async fn get_pool_stats(&self) -> Result<Vec<PoolStats>, Error> {
+        if let minera::StatsResp::Running(stat) = self.get_stats().await? {
+            Ok(stat.pools.iter().map(|p| {
+                // Share counts are kept per session, the pool's stats_id is the current one
+                let stats = p.stats.iter().find(|s| s.stats_id == p.stats_id);
+                PoolStats {
+                    url: p.url.clone(),
+                    username: p.user.clone(),
+                    status: if p.alive == 1 { PoolStatus::Alive } else { PoolStatus::Dead },
+                    active: p.active,
+                    priority: p.priority,
+                    accepted: stats.map_or(0, |s| s.accepted),
+                    rejected: stats.map_or(0, |s| s.rejected),
+                    stale: 0,
+                    difficulty_accepted: 0.0,
+                    last_share_time: None,
+                    stratum_difficulty: None,
+                }
+            }).collect())
+        } else {
+            // Not mining, so no pool is live
+            Ok(vec![])
+        }
+    }
+
+    async fn get_share_stats(&self) -> Result<ShareStats, Error> {
+        if let minera::StatsResp::Running(stat) = self.get_stats().await? {
+            Ok(ShareStats {
+                accepted: stat.totals.accepted,
+                rejected: stat.totals.rejected,
+                hardware_errors: Some(stat.totals.hw_errors),
+                ..Default::default()
+            })
+        } else {
+            // Nothing has been submitted while the miner isn't running
+            Ok(ShareStats::default())
+        }
+    }
+
     async fn get_sleep(&self) -> Result<bool, Error> {
         Err(Error::NotSupported)
     }
@@ -265,7 +339,7 @@ impl Miner for Minera {
     async fn set_sleep(&mut self, sleep: bool) -> Result<(), Error> {
         return Err(Error::NotSupported);
         let webresp = self.client.http_client
-            .get(&format!("http://{}/index.php/app/save_settings", self.ip))
+            .get(format!("http://{}/index.php/app/save_settings", self.ip))
             .query(&[("save_config", "1")])
             .send()
             .await?;
@@ -300,7 +374,7 @@ impl Miner for Minera {
         // /index.php/app/varLog
         // This returns everything, we're gonna want to subscript it
         let resp = self.client.http_client
-            .get(&format!("http://{}/index.php/app/varLog", self.ip))
+            .get(format!("http://{}/index.php/app/varLog", self.ip))
             .send()
             .await?;
         if resp.status().is_success() {
@@ -311,23 +385,41 @@ impl Miner for Minera {
         }
     }
 
+    async fn get_log_entries(&mut self) -> Result<Vec<LogEntry>, Error> {
+        Ok(log::parse_cgminer(&self.get_logs().await?))
+    }
+
     async fn get_mac(&self) -> Result<String, Error> {
-        let resp = self.client.http_client
-            .get(&format!("http://{}/index.php/app/stats", self.ip))
-            .send()
-            .await?;
-        if resp.status().is_success() {
-            let stat = resp.json::<minera::StatsResp>().await?;
-            match stat {
-                minera::StatsResp::Running(stat) => Ok(stat.mac_addr),
-                minera::StatsResp::NotRunning(stat) => Ok(stat.mac_addr),
-            }
-        } else {
-            Err(Error::HttpRequestFailed)
+        match self.get_stats().await? {
+            minera::StatsResp::Running(stat) => Ok(stat.mac_addr),
+            minera::StatsResp::NotRunning(stat) => Ok(stat.mac_addr),
         }
     }
 
-    async fn get_errors(&mut self) -> Result<Vec<String>, Error> {
+    async fn get_uptime(&self) -> Result<Duration, Error> {
+        let sysuptime = match self.get_stats().await? {
+            minera::StatsResp::Running(stat) => stat.sysuptime,
+            minera::StatsResp::NotRunning(stat) => stat.sysuptime,
+        };
+        Self::uptime_from(&sysuptime)
+    }
+
+    async fn get_network_config(&self) -> Result<NetworkConfig, Error> {
+        let ifconfig = match self.get_stats().await? {
+            minera::StatsResp::Running(stat) => stat.ifconfig,
+            minera::StatsResp::NotRunning(stat) => stat.ifconfig,
+        };
+        Ok(NetworkConfig {
+            dhcp: matches!(ifconfig.dhcp.to_lowercase().as_str(), "dhcp" | "true" | "1"),
+            ip: NetworkConfig::addr_from(&ifconfig.ip),
+            netmask: NetworkConfig::addr_from(&ifconfig.mask),
+            gateway: NetworkConfig::addr_from(&ifconfig.gw),
+            dns: NetworkConfig::dns_from(&ifconfig.dns),
+            hostname: None,
+        })
+    }
+
+    async fn get_errors(&mut self) -> Result<Vec<MinerFault>, Error> {
         // We're going to only keep the last 300 lines
         // as this returns logs from before jesus was born
         let log = self.get_logs().await?
@@ -339,12 +431,64 @@ impl Miner for Minera {
             .join("\n");
         let mut errors = HashSet::new();
         for err in MineraErrors.iter() {
-            if let Some(msg) = err.get_msg(&log) {
-                errors.insert(msg);
+            if let Some(fault) = err.get_fault(&log) {
+                errors.insert(fault);
             }
         }
         Ok(errors.into_iter().collect())
     }
+
+    async fn get_snapshot(&self) -> Result<MinerSnapshot, Error> {
+        // Everything but the pools comes from a single stats request, made alongside the pools
+        let (stats, pools) = tokio::try_join!(self.get_stats(), self.get_pools())?;
+        let (hashrate, temperature, boards, mac) = match stats {
+            minera::StatsResp::Running(stat) => (
+                Hashrate::from_hs(stat.totals.hashrate as f64),
+                Temperature::from_celsius(stat.temp),
+                Self::board_count_from(&stat),
+                stat.mac_addr,
+            ),
+            minera::StatsResp::NotRunning(stat) => (Hashrate::default(), Temperature::default(), 0, stat.mac_addr),
+        };
+        Ok(MinerSnapshot {
+            model: self.get_model().await?,
+            mac,
+            hashrate,
+            power: Some(hashrate * Efficiency::from_j_per_th(35.0)),
+            power_estimated: true,
+            efficiency: Some(Efficiency::from_j_per_th(35.0)),
+            nameplate_rate: nameplate_from_boards(boards),
+            temperature,
+            fan_speed: vec![],
+            pools: Some(pools),
+            sleep: None,
+        })
+    }
+
+    async fn get_hashboards(&self) -> Result<Vec<Hashboard>, Error> {
+        let stat = match self.get_stats().await? {
+            minera::StatsResp::Running(stat) => stat,
+            // Boards aren't reported unless the miner is running
+            minera::StatsResp::NotRunning(_) => return Ok(vec![]),
+        };
+        let devices = stat.devices;
+        Ok([devices.board_1, devices.board_2, devices.board_3, devices.board_4]
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, dev)| dev.map(|dev| Hashboard {
+                index: i,
+                online: true,
+                hashrate: Some(Hashrate::from_hs(dev.hashrate as f64)),
+                ideal_rate: None,
+                chip_count: None,
+                chip_temp: Some(Temperature::from_celsius(dev.temperature)),
+                pcb_temp: None,
+                frequency: Some(dev.frequency as f64),
+                hw_errors: Some(dev.hw_errors),
+                serial: None,
+            }))
+            .collect())
+    }
 }
 
 /// 2 fan Minervas use this interface
@@ -356,10 +500,23 @@ pub struct Minerva {
 }
 
 impl Minerva {
+    async fn get_temp_and_speed(&self) -> Result<cgminer::TempAndSpeed, Error> {
+        let resp = self.client.http_client
+            .get(format!("https://{}/api/v1/systemInfo/tempAndSpeed", self.ip))
+            .bearer_auth(&self.token)
+            .send()
+            .await?;
+        if resp.status().is_success() {
+            Ok(resp.json::<cgminer::TempAndSpeedResp>().await?.data)
+        } else {
+            Err(Error::HttpRequestFailed)
+        }
+    }
+
     /// Returns the number of hashboards detected
     async fn get_board_count(&self) -> Result<u8, Error> {
         let resp = self.client.http_client
-            .get(&format!("https://{}/api/v1/systemInfo/hashBoards", self.ip))
+            .get(format!("https://{}/api/v1/systemInfo/hashBoards", self.ip))
             .bearer_auth(&self.token)
             .send()
             .await?;
@@ -371,6 +528,19 @@ impl Minerva {
             Err(Error::HttpRequestFailed)
         }
     }
+
+    async fn get_work_mode_data(&self) -> Result<cgminer::WorkModeData, Error> {
+        let resp = self.client.http_client
+            .get(format!("https://{}/api/v1/cgminer/workMode", self.ip))
+            .bearer_auth(&self.token)
+            .send()
+            .await?;
+        if resp.status().is_success() {
+            Ok(resp.json::<cgminer::WorkModeResp>().await?.data)
+        } else {
+            Err(Error::HttpRequestFailed)
+        }
+    }
 }
 
 #[async_trait]
@@ -388,6 +558,34 @@ impl Miner for Minerva {
         "MinerVa"
     }
 
+    fn capabilities(&self) -> &'static [Capability] {
+        &[
+            Capability::ReadPools,
+            Capability::WritePools,
+            Capability::PoolStats,
+            Capability::ShareStats,
+            Capability::Uptime,
+            Capability::ReadSleep,
+            Capability::WriteSleep,
+            Capability::ReadBlink,
+            Capability::WriteBlink,
+            Capability::Logs,
+            Capability::Errors,
+            Capability::EstimatedPower,
+            Capability::Hashboards,
+            Capability::ReadWorkMode,
+            Capability::WriteWorkMode,
+            Capability::ReadNetwork,
+            Capability::SetPassword,
+            Capability::UpgradeFirmware,
+            Capability::FactoryReset,
+        ]
+    }
+
+    fn max_pools(&self) -> Option<usize> {
+        Some(3)
+    }
+
     async fn get_model(&self) -> Result<String, Error> {
         // let resp = self.client.send_recv(&self.ip, self.port, &json!({"command":"devdetails"})).await?;
         // let js = serde_json::from_str::<common::DevDetailsResp>(&resp)?;
@@ -397,7 +595,7 @@ impl Miner for Minerva {
 
     async fn auth(&mut self, username: &str, password: &str) -> Result<(), Error> {
         let resp = self.client.http_client
-            .post(&format!("https://{}/api/v1/auth/login", self.ip))
+            .post(format!("https://{}/api/v1/auth/login", self.ip))
             .json(&json!({
                 "username": username,
                 "password": password,
@@ -420,31 +618,61 @@ impl Miner for Minerva {
         }
     }
 
+    async fn set_password(&mut self, old: &str, new: &str) -> Result<(), Error> {
+        let resp = self.client.http_client
+            .post(format!("https://{}/api/v1/auth/changePassword", self.ip))
+            .bearer_auth(&self.token)
+            .json(&json!({
+                "oldPassword": old,
+                "newPassword": new,
+            }))
+            .send()
+            .await?;
+        if resp.status().is_success() {
+            Ok(())
+        } else {
+            Err(Error::HttpRequestFailed)
+        }
+    }
+
     async fn reboot(&mut self) -> Result<(), Error> {
         //TODO: This always times out as the API reboots before responding
         let resp = self.client.http_client
-            .post(&format!("https://{}:/api/v1/cgminer/reboot", self.ip))
+            .post(format!("https://{}:/api/v1/cgminer/reboot", self.ip))
             .bearer_auth(&self.token)
             .send()
             .await;
         Ok(())
     }
 
-    async fn get_hashrate(&self) -> Result<f64, Error> {
+    async fn factory_reset(&mut self, confirm_mac: &str) -> Result<(), Error> {
+        check_mac_confirmation(&self.get_mac().await?, confirm_mac)?;
+        let resp = self.client.http_client
+            .post(format!("https://{}/api/v1/systemInfo/factoryReset", self.ip))
+            .bearer_auth(&self.token)
+            .send()
+            .await?;
+        if resp.status().is_success() {
+            Ok(())
+        } else {
+            Err(Error::HttpRequestFailed)
+        }
+    }
+
+    async fn get_hashrate(&self) -> Result<Hashrate, Error> {
         let resp = self.client.http_client
-            .get(&format!("https://{}/api/v1/cgminer/summary", self.ip))
+            .get(format!("https://{}/api/v1/cgminer/summary", self.ip))
             .bearer_auth(&self.token)
             .send()
             .await?;
         if resp.status().is_success() {
             let text = resp.text().await?;
             if let Ok(summary) = serde_json::from_str::<cgminer::SummaryResp>(&text) {
-                // Convert to TH/s
-                Ok(summary.data[0].mhs_5s / 1000000.0)
+                Ok(Hashrate::from_mhs(summary.data[0].mhs_5s))
             } else if let Ok(status) = serde_json::from_str::<cgminer::ApiResp>(&text) {
                 warn!("Failed to get hashrate {}", if let Some(msg) = status.data { msg } else { "Unknown error".to_string() });
                 // The miners up but didn't give us a great response, so just return 0
-                Ok(0.0)
+                Ok(Hashrate::default())
             } else {
                 Err(Error::ApiCallFailed("Unknown error".to_string()))
             }
@@ -453,106 +681,130 @@ impl Miner for Minerva {
         }
     }
 
-    async fn get_power(&self) -> Result<f64, Error> {
+    async fn get_power(&self) -> Result<Power, Error> {
         // Guess at power consumption
         // There are 3 models with efficiencies ranging from 31 - 39 J/TH
         // Assume the middle of the road 35 J/TH
-        Ok(self.get_hashrate().await? * 35.0)
+        Ok(self.get_hashrate().await? * Efficiency::from_j_per_th(35.0))
     }
 
-    async fn get_efficiency(&self) -> Result<f64, Error> {
-        Ok(35.0)
+    async fn get_efficiency(&self) -> Result<Efficiency, Error> {
+        Ok(Efficiency::from_j_per_th(35.0))
     }
 
-    async fn get_nameplate_rate(&self) -> Result<f64, Error> {
-        // Minerva doesn't report a nameplate rate, so we have to guess
-        // There are 3 models with hashrates varying from 75 to 105 TH/s
-        // Assume the middle of the road 90 TH/s
-        // Most models are 3 board models, so assume 90/3 = 30 TH/s per board
-        // Unless we detect 4 boards in which case assume 90/4 = 22.5 TH/s per board
+    async fn get_nameplate_rate(&self) -> Result<Hashrate, Error> {
+        Ok(nameplate_from_boards(self.get_board_count().await?))
+    }
 
-        // Edge case: This will be incorrect for 4 board models with at least 1 board disconnected
-        let boards = self.get_board_count().await?;
-        Ok(boards as f64 * if boards == 4 { 22.5 } else { 30.0 })
+    async fn get_temperature(&self) -> Result<Temperature, Error> {
+        Ok(Temperature::from_celsius(self.get_temp_and_speed().await?.temperature))
     }
 
-    async fn get_temperature(&self) -> Result<f64, Error> {
+    async fn get_fan_speed(&self) -> Result<Vec<u32>, Error> {
+        let temp = self.get_temp_and_speed().await?;
+        Ok(vec![temp.fan_speed1, temp.fan_speed2])
+    }
+
+    async fn get_pools(&self) -> Result<Vec<Pool>, Error> {
         let resp = self.client.http_client
-            .get(&format!("https://{}/api/v1/systemInfo/tempAndSpeed", self.ip))
+            .get(format!("https://{}/api/v1/cgminer/poolsInSetting", self.ip))
             .bearer_auth(&self.token)
             .send()
             .await?;
         if resp.status().is_success() {
-            let temp = resp.json::<cgminer::TempAndSpeedResp>().await?;
-            Ok(temp.data.temperature)
+            let pools = resp.json::<cgminer::GetPoolsResp>().await?.data;
+            Ok([
+                (pools.pool1url, pools.pool1user),
+                (pools.pool2url, pools.pool2user),
+                (pools.pool3url, pools.pool3user),
+            ].iter()
+                .filter_map(|(url, user)| Pool::from_slot(url, user, None))
+                .collect())
         } else {
             Err(Error::HttpRequestFailed)
         }
     }
 
-    async fn get_fan_speed(&self) -> Result<Vec<u32>, Error> {
+    async fn set_pools(&mut self, pools: Vec<Pool>) -> Result<(), Error> {
+        validate_pools(&pools, self.max_pools())?;
+        let slots = pool_slots(&pools, 3);
         let resp = self.client.http_client
-            .get(&format!("https://{}/api/v1/systemInfo/tempAndSpeed", self.ip))
+            .post(format!("https://{}/api/v1/cgminer/changePool", self.ip))
             .bearer_auth(&self.token)
+            .json(&cgminer::SetPoolRequest {
+                pool1url: &slots[0].url,
+                pool1user: &slots[0].username,
+                pool1pwd: &slots[0].password,
+                pool2url: &slots[1].url,
+                pool2user: &slots[1].username,
+                pool2pwd: &slots[1].password,
+                pool3url: &slots[2].url,
+                pool3user: &slots[2].username,
+                pool3pwd: &slots[2].password,
+            })
             .send()
             .await?;
         if resp.status().is_success() {
-            let temp = resp.json::<cgminer::TempAndSpeedResp>().await?;
-            Ok(vec![temp.data.fan_speed1, temp.data.fan_speed2])
+            Ok(())
         } else {
             Err(Error::HttpRequestFailed)
         }
     }
 
-    async fn get_pools(&self) -> Result<Vec<Pool>, Error> {
+    async fn get_pool_stats(&self) -> Result<Vec<PoolStats>, Error> {
         let resp = self.client.http_client
-            .get(&format!("https://{}/api/v1/cgminer/poolsInSetting", self.ip))
+            .get(format!("https://{}/api/v1/cgminer/pools", self.ip))
             .bearer_auth(&self.token)
             .send()
             .await?;
         if resp.status().is_success() {
-            let pools = resp.json::<cgminer::GetPoolsResp>().await?;
-            let mut ret = Vec::new();
-            ret.push(Pool {
-                url: pools.data.pool1url,
-                username: pools.data.pool1user,
-                password: None,
-            });
-            ret.push(Pool {
-                url: pools.data.pool2url,
-                username: pools.data.pool2user,
-                password: None,
-            });
-            ret.push(Pool {
-                url: pools.data.pool3url,
-                username: pools.data.pool3user,
-                password: None,
-            });
-            Ok(ret)
+            let pools = resp.json::<cgminer::GetPoolsStatsResp>().await?;
+            Ok(pools.data.iter().map(|p| PoolStats {
+                url: p.url.clone(),
+                username: p.user.clone(),
+                status: PoolStatus::from_cgminer(&p.status),
+                active: p.stratum_active,
+                priority: p.priority,
+                accepted: p.accepted,
+                rejected: p.rejected,
+                stale: p.stale,
+                difficulty_accepted: p.difficulty_accepted as f64,
+                last_share_time: share_time_from(p.last_share_time),
+                stratum_difficulty: Some(p.stratum_difficulty as f64),
+            }).collect())
         } else {
             Err(Error::HttpRequestFailed)
         }
     }
 
-    async fn set_pools(&mut self, pools: Vec<Pool>) -> Result<(), Error> {
+    async fn get_share_stats(&self) -> Result<ShareStats, Error> {
         let resp = self.client.http_client
-            .post(&format!("https://{}/api/v1/cgminer/changePool", self.ip))
+            .get(format!("https://{}/api/v1/cgminer/summary", self.ip))
             .bearer_auth(&self.token)
-            .json(&cgminer::SetPoolRequest {
-                pool1url: &pools[0].url,
-                pool1user: &pools[0].username,
-                pool1pwd: if let Some(pwd) = &pools[0].password {&pwd} else {""},
-                pool2url: &pools[1].url,
-                pool2user: &pools[1].username,
-                pool2pwd: if let Some(pwd) = &pools[1].password {&pwd} else {""},
-                pool3url: &pools[2].url,
-                pool3user: &pools[2].username,
-                pool3pwd: if let Some(pwd) = &pools[2].password {&pwd} else {""},
-            })
             .send()
             .await?;
         if resp.status().is_success() {
-            Ok(())
+            let text = resp.text().await?;
+            if let Ok(summary) = serde_json::from_str::<cgminer::SummaryResp>(&text) {
+                let summary = &summary.data[0];
+                Ok(ShareStats {
+                    accepted: summary.accepted,
+                    rejected: summary.rejected,
+                    stale: Some(summary.stale),
+                    discarded: Some(summary.discarded),
+                    hardware_errors: Some(summary.hardware_errors),
+                    best_share: Some(summary.best_share),
+                    difficulty_accepted: Some(summary.difficulty_accepted as f64),
+                    difficulty_rejected: Some(summary.difficulty_rejected as f64),
+                    pool_rejected_percent: Some(summary.pool_rejected),
+                    pool_stale_percent: Some(summary.pool_stale),
+                    hardware_error_percent: Some(summary.device_hardware),
+                })
+            } else if let Ok(status) = serde_json::from_str::<cgminer::ApiResp>(&text) {
+                Err(Error::ApiCallFailed(status.data.unwrap_or_else(|| "Unknown error".to_string())))
+            } else {
+                Err(Error::ApiCallFailed("Unknown error".to_string()))
+            }
         } else {
             Err(Error::HttpRequestFailed)
         }
@@ -560,7 +812,7 @@ impl Miner for Minerva {
 
     async fn get_sleep(&self) -> Result<bool, Error> {
         let resp1 = self.client.http_client
-            .get(&format!("https://{}/api/v1/cgminer/workMode", self.ip))
+            .get(format!("https://{}/api/v1/cgminer/workMode", self.ip))
             .bearer_auth(&self.token)
             .send()
             .await?;
@@ -578,7 +830,7 @@ impl Miner for Minerva {
 
     async fn set_sleep(&mut self, sleep: bool) -> Result<(), Error> {
         let resp1 = self.client.http_client
-            .get(&format!("https://{}/api/v1/cgminer/workMode", self.ip))
+            .get(format!("https://{}/api/v1/cgminer/workMode", self.ip))
             .bearer_auth(&self.token)
             .send()
             .await?;
@@ -593,7 +845,27 @@ impl Miner for Minerva {
         data.insert("mask".to_string(), serde_json::Value::from(if sleep { "0x0" } else { "0xf" }));
         //println!("{:?}", data);
         let resp = self.client.http_client
-            .post(&format!("https://{}/api/v1/cgminer/setWorkMode", self.ip))
+            .post(format!("https://{}/api/v1/cgminer/setWorkMode", self.ip))
+            .bearer_auth(&self.token)
+            .json(&data)
+            .send()
+            .await?;
+        if resp.status().is_success() {
+            Ok(())
+        } else {
+            Err(Error::HttpRequestFailed)
+        }
+    }
+
+    async fn get_work_mode(&self) -> Result<WorkMode, Error> {
+        Ok(self.get_work_mode_data().await?.work_mode())
+    }
+
+    async fn set_work_mode(&mut self, mode: WorkMode) -> Result<(), Error> {
+        let mut data = self.get_work_mode_data().await?;
+        data.set_work_mode(mode);
+        let resp = self.client.http_client
+            .post(format!("https://{}/api/v1/cgminer/setWorkMode", self.ip))
             .bearer_auth(&self.token)
             .json(&data)
             .send()
@@ -607,7 +879,7 @@ impl Miner for Minerva {
 
     async fn get_blink(&self) -> Result<bool, Error> {
         let resp = self.client.http_client
-            .get(&format!("https://{}/api/v1/systemInfo/redLedStatus", self.ip))
+            .get(format!("https://{}/api/v1/systemInfo/redLedStatus", self.ip))
             .bearer_auth(&self.token)
             .send()
             .await?;
@@ -624,7 +896,7 @@ impl Miner for Minerva {
             status: (if blink { "1" } else { "0" }).to_string(),
         };
         let resp = self.client.http_client
-            .post(&format!("https://{}/api/v1/systemInfo/setRedLedStatus", self.ip))
+            .post(format!("https://{}/api/v1/systemInfo/setRedLedStatus", self.ip))
             .bearer_auth(&self.token)
             .json(&status)
             .send()
@@ -638,7 +910,7 @@ impl Miner for Minerva {
 
     async fn get_logs(&mut self) -> Result<Vec<String>, Error> {
         let resp = self.client.http_client
-            .get(&format!("https://{}/api/v1/cgminer/log", self.ip))
+            .get(format!("https://{}/api/v1/cgminer/log", self.ip))
             .bearer_auth(&self.token)
             .send()
             .await?;
@@ -650,9 +922,13 @@ impl Miner for Minerva {
         }
     }
 
+    async fn get_log_entries(&mut self) -> Result<Vec<LogEntry>, Error> {
+        Ok(log::parse_cgminer(&self.get_logs().await?))
+    }
+
     async fn get_mac(&self) -> Result<String, Error> {
         let resp = self.client.http_client
-            .get(&format!("https://{}/api/v1/systemInfo/network", self.ip))
+            .get(format!("https://{}/api/v1/systemInfo/network", self.ip))
             .bearer_auth(&self.token)
             .send()
             .await?;
@@ -664,14 +940,152 @@ impl Miner for Minerva {
         }
     }
 
-    async fn get_errors(&mut self) -> Result<Vec<String>, Error> {
+    async fn get_uptime(&self) -> Result<Duration, Error> {
+        let resp = self.client.http_client
+            .get(format!("https://{}/api/v1/cgminer/summary", self.ip))
+            .bearer_auth(&self.token)
+            .send()
+            .await?;
+        if resp.status().is_success() {
+            let summary = resp.json::<cgminer::SummaryResp>().await?;
+            Ok(Duration::from_secs(summary.data[0].elapsed as u64))
+        } else {
+            Err(Error::HttpRequestFailed)
+        }
+    }
+
+    async fn get_network_config(&self) -> Result<NetworkConfig, Error> {
+        let resp = self.client.http_client
+            .get(format!("https://{}/api/v1/systemInfo/network", self.ip))
+            .bearer_auth(&self.token)
+            .send()
+            .await?;
+        if resp.status().is_success() {
+            let network = resp.json::<cgminer::NetworkResponse>().await?.data;
+            let mut dns = NetworkConfig::dns_from(&network.dns);
+            dns.extend(NetworkConfig::dns_from(&network.dnsBak));
+            Ok(NetworkConfig {
+                dhcp: network.dhcp4,
+                ip: NetworkConfig::addr_from(&network.ip),
+                netmask: NetworkConfig::addr_from(&network.netmask),
+                gateway: NetworkConfig::addr_from(&network.gateway),
+                dns,
+                hostname: None,
+            })
+        } else {
+            Err(Error::HttpRequestFailed)
+        }
+    }
+
+    async fn get_errors(&mut self) -> Result<Vec<MinerFault>, Error> {
         let log = self.get_logs().await?.join("\n");
         let mut errors = HashSet::new();
         for err in MinerVaErrors.iter() {
-            if let Some(msg) = err.get_msg(&log) {
-                errors.insert(msg);
+            if let Some(fault) = err.get_fault(&log) {
+                errors.insert(fault);
             }
         }
         Ok(errors.into_iter().collect())
     }
+
+    async fn get_snapshot(&self) -> Result<MinerSnapshot, Error> {
+        // Each value is its own endpoint, so fetch them all at once
+        let (model, mac, hashrate, temp, nameplate_rate, pools, sleep) = tokio::try_join!(
+            self.get_model(),
+            self.get_mac(),
+            self.get_hashrate(),
+            self.get_temp_and_speed(),
+            self.get_nameplate_rate(),
+            self.get_pools(),
+            self.get_sleep(),
+        )?;
+        Ok(MinerSnapshot {
+            model,
+            mac,
+            hashrate,
+            power: Some(hashrate * Efficiency::from_j_per_th(35.0)),
+            power_estimated: true,
+            efficiency: Some(Efficiency::from_j_per_th(35.0)),
+            nameplate_rate,
+            temperature: Temperature::from_celsius(temp.temperature),
+            fan_speed: vec![temp.fan_speed1, temp.fan_speed2],
+            pools: Some(pools),
+            sleep: Some(sleep),
+        })
+    }
+
+    async fn get_hashboards(&self) -> Result<Vec<Hashboard>, Error> {
+        let resp = self.client.http_client
+            .get(format!("https://{}/api/v1/systemInfo/hashBoards", self.ip))
+            .bearer_auth(&self.token)
+            .send()
+            .await?;
+        if resp.status().is_success() {
+            let resp = resp.json::<cgminer::HashBoardsResp>().await?;
+            let boards = resp.data.ok_or(Error::ApiCallFailed(resp.message))?;
+            Ok(boards.iter().map(|board| Hashboard {
+                index: board.id as usize,
+                online: board.online,
+                hashrate: None,
+                ideal_rate: None,
+                chip_count: None,
+                chip_temp: None,
+                pcb_temp: Some(Temperature::from_celsius(board.temperature as f64)),
+                frequency: None,
+                hw_errors: None,
+                serial: None,
+            }).collect())
+        } else {
+            Err(Error::HttpRequestFailed)
+        }
+    }
+
+    async fn upgrade_firmware(&mut self, image: &[u8], progress: &(dyn Fn(UpgradeStage) + Send + Sync)) -> Result<(), Error> {
+        progress(UpgradeStage::Uploading);
+        let (content_type, body) = multipart::file_form("file", "firmware.bin", image);
+        let len = body.len();
+        let (body, mut uploaded) = upload::tracked_body(body);
+        let send = self.client.http_client
+            .post(format!("https://{}/api/v1/systemInfo/upgrade", self.ip))
+            .bearer_auth(&self.token)
+            .header("Content-Type", content_type)
+            .header("Content-Length", len)
+            .body(body)
+            .timeout(UPGRADE_TIMEOUT)
+            .send();
+        // Like the Antminer CGI, the response only comes once the image is flashed
+        tokio::pin!(send);
+        let mut flashing = false;
+        let resp = loop {
+            tokio::select! {
+                resp = &mut send => break resp?,
+                done = &mut uploaded, if !flashing => {
+                    flashing = true;
+                    if done.is_ok() {
+                        progress(UpgradeStage::Flashing);
+                    }
+                },
+            }
+        };
+        if !resp.status().is_success() {
+            return Err(Error::HttpRequestFailed);
+        }
+        progress(UpgradeStage::Rebooting);
+        self.client.wait_for_reboot(&self.ip, 443).await?;
+        progress(UpgradeStage::Online);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_parses_fractional_uptime() {
+        assert_eq!(Minera::uptime_from("5817.42").unwrap(), Duration::from_millis(5_817_420));
+        assert_eq!(Minera::uptime_from("86400\n").unwrap(), Duration::from_secs(86400));
+        assert!(matches!(Minera::uptime_from("-1"), Err(Error::InvalidResponse)));
+        assert!(matches!(Minera::uptime_from("up 2 days"), Err(Error::InvalidResponse)));
+    }
 }
@@ -1,282 +1,439 @@
 use lazy_regex::regex;
 
-use crate::miner::MinerError;
+use crate::miner::{MinerError, FaultCategory, FaultSeverity};
 
 pub static WhatsminerErrors: [MinerError; 67] = [
     MinerError {
         re: regex!(r"1[0-3](0|1)"),
         msg: "Fan {} speed error",
+        category: FaultCategory::Fan,
+        severity: FaultSeverity::Error,
     },
     MinerError {
         re: regex!(r"140"),
         msg: "Fan speed too high",
+        category: FaultCategory::Fan,
+        severity: FaultSeverity::Warning,
     },
 
     MinerError {
         re: regex!(r"200"),
         msg: "No power found",
+        category: FaultCategory::Power,
+        severity: FaultSeverity::Critical,
     },
     MinerError {
         re: regex!(r"201"),
         msg: "Power configuration mismatch",
+        category: FaultCategory::Power,
+        severity: FaultSeverity::Error,
     },
     MinerError {
         re: regex!(r"202"),
         msg: "Power output voltage error",
+        category: FaultCategory::Power,
+        severity: FaultSeverity::Error,
     },
     MinerError {
         re: regex!(r"20[3,4]"),
         msg: "Power protection triggered",
+        category: FaultCategory::Power,
+        severity: FaultSeverity::Error,
     },
     MinerError {
         re: regex!(r"205"),
         msg: "Power current error",
+        category: FaultCategory::Power,
+        severity: FaultSeverity::Error,
     },
     MinerError {
         re: regex!(r"206"),
         msg: "Low input voltage",
+        category: FaultCategory::Power,
+        severity: FaultSeverity::Error,
     },
     MinerError {
         re: regex!(r"207"),
         msg: "Input current protection",
+        category: FaultCategory::Power,
+        severity: FaultSeverity::Error,
     },
     MinerError {
         re: regex!(r"210"),
         msg: "Power error status",
+        category: FaultCategory::Power,
+        severity: FaultSeverity::Error,
     },
     MinerError {
         re: regex!(r"213"),
         msg: "Input voltage and current do not match",
+        category: FaultCategory::Power,
+        severity: FaultSeverity::Error,
     },
     MinerError {
         re: regex!(r"216"),
         msg: "Power remained unchanged",
+        category: FaultCategory::Power,
+        severity: FaultSeverity::Warning,
     },
     MinerError {
         re: regex!(r"217"),
         msg: "Power enable error",
+        category: FaultCategory::Power,
+        severity: FaultSeverity::Error,
     },
     MinerError {
         re: regex!(r"218"),
         msg: "Input voltage below 230V in high-perf mode",
+        category: FaultCategory::Power,
+        severity: FaultSeverity::Warning,
     },
     MinerError {
         re: regex!(r"23[3-5]"),
         msg: "Power output over-temperature",
+        category: FaultCategory::Power,
+        severity: FaultSeverity::Error,
     },
     MinerError {
         re: regex!(r"23[6-8]|268"),
         msg: "Power output overcurrent",
+        category: FaultCategory::Power,
+        severity: FaultSeverity::Error,
     },
     MinerError {
         re: regex!(r"239"),
         msg: "Power output over voltage",
+        category: FaultCategory::Power,
+        severity: FaultSeverity::Error,
     },
     MinerError {
         re: regex!(r"240"),
         msg: "Power output under voltage",
+        category: FaultCategory::Power,
+        severity: FaultSeverity::Error,
     },
     MinerError {
         re: regex!(r"241"),
         msg: "Power output current imbalance",
+        category: FaultCategory::Power,
+        severity: FaultSeverity::Error,
     },
     MinerError {
         re: regex!(r"24[3-5]"),
         msg: "Power input over-temperature",
+        category: FaultCategory::Power,
+        severity: FaultSeverity::Error,
     },
     MinerError {
         re: regex!(r"24[6,7]|269"),
         msg: "Power input overcurrent",
+        category: FaultCategory::Power,
+        severity: FaultSeverity::Error,
     },
     MinerError {
         re: regex!(r"24[8,9]|270"),
         msg: "Power input over voltage",
+        category: FaultCategory::Power,
+        severity: FaultSeverity::Error,
     },
     MinerError {
         re: regex!(r"25[0,1]|271"),
         msg: "Power input under voltage",
+        category: FaultCategory::Power,
+        severity: FaultSeverity::Error,
     },
     MinerError {
         re: regex!(r"25[3,4]"),
         msg: "PSU fan error",
+        category: FaultCategory::Power,
+        severity: FaultSeverity::Error,
     },
     MinerError {
         re: regex!(r"25[5,6]"),
         msg: "Power output over power",
+        category: FaultCategory::Power,
+        severity: FaultSeverity::Error,
     },
     MinerError {
         re: regex!(r"257"),
         msg: "Input overcurrent protection on primary",
+        category: FaultCategory::Power,
+        severity: FaultSeverity::Error,
     },
     MinerError {
         re: regex!(r"263"),
         msg: "Power communication warning",
+        category: FaultCategory::Power,
+        severity: FaultSeverity::Warning,
     },
     MinerError {
         re: regex!(r"264"),
         msg: "Power communication error",
+        category: FaultCategory::Power,
+        severity: FaultSeverity::Error,
     },
     MinerError {
         re: regex!(r"267"),
         msg: "Power watchdog error",
+        category: FaultCategory::Power,
+        severity: FaultSeverity::Error,
     },
     MinerError {
         re: regex!(r"272"),
         msg: "Excessive power output warning",
+        category: FaultCategory::Power,
+        severity: FaultSeverity::Warning,
     },
     MinerError {
         re: regex!(r"273"),
         msg: "Power input power too high",
+        category: FaultCategory::Power,
+        severity: FaultSeverity::Error,
     },
     MinerError {
         re: regex!(r"274"),
         msg: "PSU fan warning",
+        category: FaultCategory::Power,
+        severity: FaultSeverity::Warning,
     },
     MinerError {
         re: regex!(r"275"),
         msg: "PSU over-temperature warning",
+        category: FaultCategory::Power,
+        severity: FaultSeverity::Warning,
     },
 
     MinerError {
         re: regex!(r"30([0-2])"),
         msg: "Board {} temperature sensor error",
+        category: FaultCategory::Temperature,
+        severity: FaultSeverity::Error,
     },
     MinerError {
         re: regex!(r"32([0-2])"),
         msg: "Board {} temperature reading error",
+        category: FaultCategory::Temperature,
+        severity: FaultSeverity::Error,
     },
     MinerError {
         re: regex!(r"329"),
         msg: "Control board temperature sensor error",
+        category: FaultCategory::Temperature,
+        severity: FaultSeverity::Warning,
     },
     MinerError {
         re: regex!(r"35([0-2])"),
         msg: "Board {} overheating",
+        category: FaultCategory::Temperature,
+        severity: FaultSeverity::Critical,
     },
     MinerError {
         re: regex!(r"360"),
         msg: "Board overheating",
+        category: FaultCategory::Temperature,
+        severity: FaultSeverity::Critical,
     },
 
     MinerError {
         re: regex!(r"41([0-2])"),
         msg: "Board {} EEPROM detect error",
+        category: FaultCategory::Eeprom,
+        severity: FaultSeverity::Error,
     },
     MinerError {
         re: regex!(r"42([0-2])"),
         msg: "Board {} EEPROM parse error",
+        category: FaultCategory::Eeprom,
+        severity: FaultSeverity::Error,
     },
     MinerError {
         re: regex!(r"43([0-2])"),
         msg: "Board {} EEPROM chip bin type error",
+        category: FaultCategory::Eeprom,
+        severity: FaultSeverity::Error,
     },
     MinerError {
         re: regex!(r"44([0-2])"),
         msg: "Board {} EEPROM chip number error",
+        category: FaultCategory::Eeprom,
+        severity: FaultSeverity::Error,
     },
     MinerError {
         re: regex!(r"45([0-2])"),
         msg: "Board {} EEPROM transfer error",
+        category: FaultCategory::Eeprom,
+        severity: FaultSeverity::Error,
     },
     MinerError {
         re: regex!(r"51([0-2])"),
         msg: "Board {} type error",
+        category: FaultCategory::Hashboard,
+        severity: FaultSeverity::Error,
     },
     MinerError {
         re: regex!(r"52([0-2])"),
         msg: "Board {} bin type error",
+        category: FaultCategory::Hashboard,
+        severity: FaultSeverity::Error,
     },
     MinerError {
         re: regex!(r"53([0-2])"),
         msg: "Board {} not found",
+        category: FaultCategory::Hashboard,
+        severity: FaultSeverity::Error,
     },
     MinerError {
         re: regex!(r"54([0-2])"),
         msg: "Board {} read chip id error",
+        category: FaultCategory::Chip,
+        severity: FaultSeverity::Error,
     },
     MinerError {
         re: regex!(r"55([0-2])"),
         msg: "Board {} bad chip",
+        category: FaultCategory::Chip,
+        severity: FaultSeverity::Error,
     },
     MinerError {
         re: regex!(r"56([0-2])"),
         msg: "Board {} loss balance",
+        category: FaultCategory::Hashboard,
+        severity: FaultSeverity::Error,
     },
     MinerError {
         re: regex!(r"511([0-2])"),
         msg: "Board {} frequency up timeout",
+        category: FaultCategory::Hashboard,
+        severity: FaultSeverity::Warning,
     },
     MinerError {
         re: regex!(r"507([0-2])"),
         msg: "Board {} water velocity abnormal",
+        category: FaultCategory::Temperature,
+        severity: FaultSeverity::Critical,
     },
 
     MinerError {
         re: regex!(r"600"),
         msg: "Overheating",
+        category: FaultCategory::Temperature,
+        severity: FaultSeverity::Critical,
     },
     MinerError {
         re: regex!(r"610"),
         msg: "Temperature too high in high-perf mode",
+        category: FaultCategory::Temperature,
+        severity: FaultSeverity::Warning,
     },
 
     MinerError {
         re: regex!(r"701"),
         msg: "Control board no support chip",
+        category: FaultCategory::ControlBoard,
+        severity: FaultSeverity::Error,
     },
     MinerError {
         re: regex!(r"710|712"),
         msg: "Control board rebooted as exception",
+        category: FaultCategory::ControlBoard,
+        severity: FaultSeverity::Warning,
     },
 
     MinerError {
         re: regex!(r"800"),
         msg: "Cgminer checksum error",
+        category: FaultCategory::Firmware,
+        severity: FaultSeverity::Critical,
     },
     MinerError {
         re: regex!(r"801"),
         msg: "System-monitor checksum error",
+        category: FaultCategory::Firmware,
+        severity: FaultSeverity::Critical,
     },
     MinerError {
         re: regex!(r"802"),
         msg: "Remote-daemon checksum error",
+        category: FaultCategory::Firmware,
+        severity: FaultSeverity::Critical,
     },
 
     MinerError {
         re: regex!(r"2000"),
         msg: "No pools configured",
+        category: FaultCategory::Pool,
+        severity: FaultSeverity::Critical,
     },
     MinerError {
         re: regex!(r"2010"),
         msg: "All pools disabled",
+        category: FaultCategory::Pool,
+        severity: FaultSeverity::Critical,
     },
     MinerError {
-        re: regex!(r"202[0-2]"),
+        re: regex!(r"202([0-2])"),
         msg: "Pool {} connect failure",
+        category: FaultCategory::Pool,
+        severity: FaultSeverity::Error,
     },
     MinerError {
         re: regex!(r"2030"),
         msg: "High pool reject rate",
+        category: FaultCategory::Pool,
+        severity: FaultSeverity::Warning,
     },
     MinerError {
         re: regex!(r"2040"),
         msg: "Pool does not support asicboost",
+        category: FaultCategory::Pool,
+        severity: FaultSeverity::Warning,
     },
     MinerError {
         re: regex!(r"23[1,2]0"),
         msg: "Hashrate too low",
+        category: FaultCategory::Hashboard,
+        severity: FaultSeverity::Warning,
     },
     MinerError {
         re: regex!(r"24[1,2]0"),
         msg: "Hashrate loss is too high",
+        category: FaultCategory::Hashboard,
+        severity: FaultSeverity::Warning,
     },
 
     MinerError {
         re: regex!(r"8410"),
         msg: "Incorrect firmware version",
+        category: FaultCategory::Firmware,
+        severity: FaultSeverity::Critical,
     },
     MinerError {
         re: regex!(r"10000[0-3]"),
         msg: "Corrupted firmware signature",
+        category: FaultCategory::Firmware,
+        severity: FaultSeverity::Critical,
     },
 ];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_code_fault() {
+        // 2010 must not be mistaken for 201
+        let faults = WhatsminerErrors.iter()
+            .filter_map(|err| err.get_code_fault("2010", None))
+            .collect::<Vec<_>>();
+        assert_eq!(faults.len(), 1);
+        assert_eq!(faults[0].message, "All pools disabled");
+        assert_eq!(faults[0].category, FaultCategory::Pool);
+        assert_eq!(faults[0].code.as_deref(), Some("2010"));
+
+        let fault = WhatsminerErrors.iter()
+            .find_map(|err| err.get_code_fault("351", None))
+            .unwrap();
+        assert_eq!(fault.message, "Board 1 overheating");
+        assert_eq!(fault.component, Some(1));
+    }
+}
@@ -4,7 +4,12 @@ use serde_json::json;
 use tokio::{net::TcpStream, io::{AsyncWriteExt, AsyncReadExt}};
 use lazy_regex::regex;
 use std::collections::HashSet;
-use crate::{Client, Miner, error::Error, Pool, miners::common, miners::whatsminer::wmapi};
+use std::time::Duration;
+use chrono::{Datelike, NaiveDateTime};
+use crate::{BoardTuning, Capability, Environment, FanConfig, Tuning, TuningConfig, UpgradeStage, WorkMode, Client, Hashboard, LogEntry, Miner, MinerFault, MinerSnapshot, NetworkConfig, error::Error, Pool, PoolStats, PoolStatus, PsuInfo, ShareStats, miners::common, miners::whatsminer::wmapi};
+use crate::util::log;
+use crate::miner::{check_mac_confirmation, pool_slots, share_time_from, validate_pools, UPGRADE_TIMEOUT};
+use crate::units::{Efficiency, Hashrate, Power, Temperature};
 
 use super::{error::WhatsminerErrors, wmapi::StatusCode};
 
@@ -37,6 +42,27 @@ pub struct Whatsminer {
 }
 
 impl Whatsminer {
+    /// btminer rejects anything but 1 to 8 letters, numbers or underscores
+    fn check_password(password: &str) -> Result<(), Error> {
+        if password.is_empty() || password.len() > 8 || !password.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(Error::InvalidArgument("Password must be 1 to 8 letters, numbers or underscores".to_string()));
+        }
+        Ok(())
+    }
+
+    /// btminer only takes a global percentage of the stock frequency, None if there's nothing to set
+    fn target_freq_percent(board: Option<usize>, tuning: &TuningConfig) -> Result<Option<i8>, Error> {
+        if board.is_some() || tuning.frequency.is_some() || tuning.voltage.is_some() {
+            return Err(Error::NotSupported);
+        }
+        match tuning.frequency_offset {
+            Some(percent) if !(-100..=100).contains(&percent) => {
+                Err(Error::InvalidArgument(format!("Frequency offset {}% is outside -100% to 100%", percent)))
+            },
+            percent => Ok(percent),
+        }
+    }
+
     async fn send_recv<T>(&self, data: &T) -> Result<String, Error>
         where T: ToString
     {
@@ -48,6 +74,11 @@ impl Whatsminer {
         Ok(resp)
     }
 
+    async fn get_summary(&self) -> Result<wmapi::SummaryResp, Error> {
+        let resp = self.send_recv(&json!({"cmd":"summary"})).await?;
+        Ok(serde_json::from_str(&resp)?)
+    }
+
     async fn refresh_token(&mut self) -> Result<(), Error> {
         if let Some(passwd) = &self.password {
             let resp = self.send_recv(&json!({"cmd": "get_token"})).await?;
@@ -105,6 +136,45 @@ impl Miner for Whatsminer {
         "Whatsminer"
     }
 
+    fn capabilities(&self) -> &'static [Capability] {
+        &[
+            Capability::ReadPools,
+            Capability::WritePools,
+            Capability::PoolStats,
+            Capability::ShareStats,
+            Capability::FirmwareVersion,
+            Capability::SerialNumber,
+            Capability::Uptime,
+            Capability::PsuInfo,
+            Capability::Environment,
+            Capability::ReadSleep,
+            Capability::WriteSleep,
+            Capability::ReadBlink,
+            Capability::WriteBlink,
+            Capability::Logs,
+            Capability::Errors,
+            Capability::MeasuredPower,
+            Capability::Hashboards,
+            Capability::ReadPowerLimit,
+            Capability::WritePowerLimit,
+            Capability::ReadWorkMode,
+            Capability::WriteWorkMode,
+            Capability::ReadFanConfig,
+            Capability::WriteFanConfig,
+            Capability::ReadTuning,
+            Capability::WriteTuning,
+            Capability::ReadNetwork,
+            Capability::WriteNetwork,
+            Capability::SetPassword,
+            Capability::UpgradeFirmware,
+            Capability::FactoryReset,
+        ]
+    }
+
+    fn max_pools(&self) -> Option<usize> {
+        Some(3)
+    }
+
     async fn get_model(&self) -> Result<String, Error> {
         let resp = self.client.http_client
             .get(format!("https://{}/cgi-bin/luci/admin/status/overview", self.ip))
@@ -135,6 +205,24 @@ impl Miner for Whatsminer {
         Ok(())
     }
 
+    async fn set_password(&mut self, old: &str, new: &str) -> Result<(), Error> {
+        // Check before we lose the token
+        Self::check_password(new)?;
+        let js = json!({
+            "cmd": "update_pwd",
+            "old": old,
+            "new": new,
+        });
+        let resp = self.send_recv_enc(js).await?;
+        let stat = serde_json::from_str::<wmapi::Status>(&resp)?;
+        if stat.status != StatusCode::SUCC {
+            return Err(Error::ApiCallFailed(stat.msg));
+        }
+        // The old token was derived from the old password
+        self.password = Some(new.to_string());
+        self.refresh_token().await
+    }
+
     async fn reboot(&mut self) -> Result<(), Error> {
         let js = json!({
             "command": "reboot",
@@ -143,83 +231,189 @@ impl Miner for Whatsminer {
         Ok(())
     }
 
-    async fn get_hashrate(&self) -> Result<f64, Error> {
+    async fn factory_reset(&mut self, confirm_mac: &str) -> Result<(), Error> {
+        check_mac_confirmation(&self.get_mac().await?, confirm_mac)?;
+        let resp = self.send_recv_enc(json!({"cmd": "factory_reset"})).await?;
+        let stat = serde_json::from_str::<wmapi::Status>(&resp)?;
+        if stat.status == StatusCode::SUCC {
+            Ok(())
+        } else {
+            Err(Error::ApiCallFailed(stat.msg))
+        }
+    }
+
+    async fn get_hashrate(&self) -> Result<Hashrate, Error> {
         let resp = self.send_recv(&json!({"cmd":"summary"})).await?;
-        if let Ok(status) = serde_json::from_str::<wmapi::Status>(&resp) {
+        if let Ok(_status) = serde_json::from_str::<wmapi::Status>(&resp) {
             // We could error or assume not hashing
             // Err(Error::ApiCallFailed(status.msg))
-            Ok(0.0)
+            Ok(Hashrate::default())
         } else {
             let sum: wmapi::SummaryResp = serde_json::from_str(&resp)?;
-            Ok(sum.summary[0].hs_rt / 1000000.0)
+            Ok(Hashrate::from_mhs(sum.summary[0].hs_rt))
         }
     }
 
-    async fn get_power(&self) -> Result<f64, Error> {
-        let resp = self.send_recv(&json!({"cmd":"summary"})).await?;
-        let sum: wmapi::SummaryResp = serde_json::from_str(&resp)?;
-        Ok(sum.summary[0].power as f64)
+    async fn get_power(&self) -> Result<Power, Error> {
+        let sum = self.get_summary().await?;
+        Ok(Power::from_watts(sum.summary[0].power as f64))
     }
 
-    async fn get_efficiency(&self) -> Result<f64, Error> {
+    async fn get_efficiency(&self) -> Result<Efficiency, Error> {
         let resp = self.send_recv(&json!({"cmd":"summary"})).await?;
-        let sum: wmapi::SummaryResp = serde_json::from_str(&resp)?;
-        if let Ok(status) = serde_json::from_str::<wmapi::Status>(&resp) {
-            // If we're not hashing we can't calculate efficiency
-            Ok(f64::INFINITY)
-        } else {
-            let sum: wmapi::SummaryResp = serde_json::from_str(&resp)?;
-            Ok(sum.summary[0].power as f64 / (sum.summary[0].hs_rt / 1000000.0))
+        if serde_json::from_str::<wmapi::Status>(&resp).is_ok() {
+            // A sleeping miner answers with a bare status
+            return Err(Error::NotHashing);
         }
+        let sum: wmapi::SummaryResp = serde_json::from_str(&resp)?;
+        (Power::from_watts(sum.summary[0].power as f64) / Hashrate::from_mhs(sum.summary[0].hs_rt)).ok_or(Error::NotHashing)
     }
 
-    async fn get_nameplate_rate(&self) -> Result<f64, Error> {
-        let resp = self.send_recv(&json!({"cmd":"summary"})).await?;
-        let hash: wmapi::SummaryResp = serde_json::from_str(&resp)?;
-        Ok(hash.summary[0].factory_ghs as f64 / 1000.0)
+    async fn get_nameplate_rate(&self) -> Result<Hashrate, Error> {
+        let hash = self.get_summary().await?;
+        Ok(Hashrate::from_ghs(hash.summary[0].factory_ghs as f64))
     }
 
-    async fn get_temperature(&self) -> Result<f64, Error> {
-        let resp = self.send_recv(&json!({"cmd":"summary"})).await?;
-        let sum: wmapi::SummaryResp = serde_json::from_str(&resp)?;
-        Ok(sum.summary[0].temperature)
+    async fn get_temperature(&self) -> Result<Temperature, Error> {
+        let sum = self.get_summary().await?;
+        Ok(Temperature::from_celsius(sum.summary[0].temperature))
+    }
+
+    async fn get_psu_info(&self) -> Result<PsuInfo, Error> {
+        let summary = &self.get_summary().await?.summary[0];
+        Ok(PsuInfo {
+            input_voltage: None,
+            output_voltage: summary.voltage.map(|mv| mv as f64 / 1000.0),
+            current: summary.power_current,
+            power: Some(Power::from_watts(summary.power as f64)),
+            fan_speed: summary.power_fanspeed.map(|rpm| rpm as u32),
+        })
+    }
+
+    async fn get_environment(&self) -> Result<Environment, Error> {
+        let summary = &self.get_summary().await?.summary[0];
+        Ok(Environment {
+            inlet_temp: summary.env_temp.map(Temperature::from_celsius),
+            outlet_temp: None,
+        })
+    }
+
+    async fn get_power_limit(&self) -> Result<Power, Error> {
+        let sum = self.get_summary().await?;
+        Ok(Power::from_watts(sum.summary[0].power_limit as f64))
+    }
+
+    async fn set_power_limit(&mut self, limit: Power) -> Result<(), Error> {
+        // btminer restarts to apply the new limit
+        let js = json!({
+            "cmd": "adjust_power_limit",
+            "power_limit": (limit.as_watts().round() as u32).to_string(),
+        });
+        let resp = self.send_recv_enc(js).await?;
+        let stat = serde_json::from_str::<wmapi::Status>(&resp)?;
+        if stat.status == StatusCode::SUCC {
+            Ok(())
+        } else {
+            Err(Error::ApiCallFailed(stat.msg))
+        }
     }
 
     async fn get_fan_speed(&self) -> Result<Vec<u32>, Error> {
-        let resp = self.send_recv(&json!({"cmd":"summary"})).await?;
-        let sum: wmapi::SummaryResp = serde_json::from_str(&resp)?;
+        let sum = self.get_summary().await?;
         Ok(vec![sum.summary[0].fan_speed_in, sum.summary[0].fan_speed_out])
     }
 
+    async fn get_fan_config(&self) -> Result<FanConfig, Error> {
+        // btminer has no fixed speed mode, the fans are either automatic or off
+        let sum = self.get_summary().await?;
+        match sum.summary[0].liquid_cooling {
+            Some(true) => Ok(FanConfig::Immersion),
+            _ => Ok(FanConfig::Auto),
+        }
+    }
+
+    async fn set_fan_config(&mut self, config: FanConfig) -> Result<(), Error> {
+        let zero_speed = match config {
+            FanConfig::Auto => "0",
+            FanConfig::Immersion => "1",
+            FanConfig::Fixed(_) => return Err(Error::NotSupported),
+        };
+        let js = json!({
+            "cmd": "set_fan_zero_speed",
+            "fan_zero_speed": zero_speed,
+        });
+        let resp = self.send_recv_enc(js).await?;
+        let stat = serde_json::from_str::<wmapi::Status>(&resp)?;
+        if stat.status == StatusCode::SUCC {
+            Ok(())
+        } else {
+            Err(Error::ApiCallFailed(stat.msg))
+        }
+    }
+
     async fn get_pools(&self) -> Result<Vec<Pool>, Error> {
         let resp = self.send_recv(&json!({"cmd":"pools"})).await?;
         let pools: common::PoolsResp = serde_json::from_str(&resp)?;
-        Ok(pools.pools.iter().map(|p| Pool {
-            url: p.url.clone(),
-            username: p.user.clone(),
-            password: None,
-        }).collect())
+        Ok(pools.pools.iter()
+            .filter_map(|p| Pool::from_slot(&p.url, &p.user, None))
+            .collect())
     }
 
     async fn set_pools(&mut self, pools: Vec<Pool>) -> Result<(), Error> {
-        //TODO: this can panic
+        validate_pools(&pools, self.max_pools())?;
+        let slots = pool_slots(&pools, 3);
         let js = json!({
             "cmd": "update_pools",
-            "pool1": pools[0].url,
-            "worker1": pools[0].username,
-            "passwd1": pools[0].password,
-            "pool2": pools[1].url,
-            "worker2": pools[1].username,
-            "passwd2": pools[1].password,
-            "pool3": pools[2].url,
-            "worker3": pools[2].username,
-            "passwd3": pools[2].password,
+            "pool1": slots[0].url,
+            "worker1": slots[0].username,
+            "passwd1": slots[0].password,
+            "pool2": slots[1].url,
+            "worker2": slots[1].username,
+            "passwd2": slots[1].password,
+            "pool3": slots[2].url,
+            "worker3": slots[2].username,
+            "passwd3": slots[2].password,
         });
         let resp = self.send_recv_enc(js).await?;
         //println!("{}", resp);
         Ok(())
     }
 
+    async fn get_pool_stats(&self) -> Result<Vec<PoolStats>, Error> {
+        let resp = self.send_recv(&json!({"cmd":"pools"})).await?;
+        let pools: common::PoolsResp = serde_json::from_str(&resp)?;
+        Ok(pools.pools.iter().map(|p| PoolStats {
+            url: p.url.clone(),
+            username: p.user.clone(),
+            status: PoolStatus::from_cgminer(&p.status),
+            active: p.stratum_active,
+            priority: p.priority,
+            accepted: p.accepted,
+            rejected: p.rejected,
+            stale: p.stale,
+            difficulty_accepted: p.difficulty_accepted,
+            last_share_time: share_time_from(p.last_share_time),
+            stratum_difficulty: Some(p.stratum_difficulty),
+        }).collect())
+    }
+
+    async fn get_share_stats(&self) -> Result<ShareStats, Error> {
+        let summary = &self.get_summary().await?.summary[0];
+        Ok(ShareStats {
+            accepted: summary.accepted,
+            rejected: summary.rejected,
+            stale: summary.stale,
+            discarded: summary.discarded,
+            hardware_errors: summary.hardware_errors,
+            best_share: summary.best_share,
+            difficulty_accepted: summary.difficulty_accepted,
+            difficulty_rejected: summary.difficulty_rejected,
+            pool_rejected_percent: Some(summary.pool_rejected_per),
+            pool_stale_percent: Some(summary.pool_stale_per),
+            hardware_error_percent: summary.device_hardware_per,
+        })
+    }
+
     async fn get_sleep(&self) -> Result<bool, Error> {
         //This doesn't work for miners running cgminer
         let resp = self.send_recv(&json!({"cmd":"status"})).await?;
@@ -230,7 +424,7 @@ impl Miner for Whatsminer {
                 // Double check that cgminer isn't running
                 // Scrape the web API yet again
                 let r = self.client.http_client
-                .get(&format!("https://{}/cgi-bin/luci/admin/status/processes", self.ip))
+                .get(format!("https://{}/cgi-bin/luci/admin/status/processes", self.ip))
                     .send()
                     .await?
                     .text()
@@ -260,6 +454,40 @@ impl Miner for Whatsminer {
         }
     }
 
+    async fn get_work_mode(&self) -> Result<WorkMode, Error> {
+        if self.get_sleep().await? {
+            return Ok(WorkMode::Sleep);
+        }
+        let sum = self.get_summary().await?;
+        match sum.summary[0].power_mode.as_str() {
+            "Low" => Ok(WorkMode::LowPower),
+            "Normal" => Ok(WorkMode::Normal),
+            "High" => Ok(WorkMode::HighPerformance),
+            mode => Err(Error::ApiCallFailed(format!("Unknown power mode {}", mode))),
+        }
+    }
+
+    async fn set_work_mode(&mut self, mode: WorkMode) -> Result<(), Error> {
+        let cmd = match mode {
+            WorkMode::Sleep => return self.set_sleep(true).await,
+            WorkMode::LowPower => "set_low_power",
+            WorkMode::Normal => "set_normal_power",
+            WorkMode::HighPerformance => "set_high_power",
+            WorkMode::Vendor(_) => return Err(Error::NotSupported),
+        };
+        // The power mode commands don't wake a sleeping miner
+        if self.get_sleep().await? {
+            self.set_sleep(false).await?;
+        }
+        let resp = self.send_recv_enc(json!({"cmd": cmd})).await?;
+        let stat = serde_json::from_str::<wmapi::Status>(&resp)?;
+        if stat.status == StatusCode::SUCC {
+            Ok(())
+        } else {
+            Err(Error::ApiCallFailed(stat.msg))
+        }
+    }
+
     async fn get_blink(&self) -> Result<bool, Error> {
         let resp = self.send_recv(&json!({"cmd":"get_miner_info"})).await?;
         if let Ok(status) = serde_json::from_str::<wmapi::Status>(&resp) {
@@ -323,6 +551,10 @@ impl Miner for Whatsminer {
         }
     }
 
+    async fn get_log_entries(&mut self) -> Result<Vec<LogEntry>, Error> {
+        Ok(log::parse_whatsminer(&self.get_logs().await?))
+    }
+
     async fn get_mac(&self) -> Result<String, Error> {
         let resp = self.send_recv(&json!({"cmd":"get_miner_info"})).await?;
         if let Ok(_) = serde_json::from_str::<wmapi::Status>(&resp) {
@@ -336,7 +568,78 @@ impl Miner for Whatsminer {
         }
     }
 
-    async fn get_errors(&mut self) -> Result<Vec<String>, Error> {
+    async fn get_firmware_version(&self) -> Result<String, Error> {
+        if let Some(version) = self.get_summary().await?.summary[0].firmware_version.clone() {
+            return Ok(version);
+        }
+        // Older firmware only reports it in status
+        let resp = self.send_recv(&json!({"cmd":"status"})).await?;
+        let btstatus: wmapi::BtStatusResp = serde_json::from_str(&resp)?;
+        Ok(btstatus.msg.firmware_version)
+    }
+
+    async fn get_serial_number(&self) -> Result<String, Error> {
+        let resp = self.send_recv(&json!({"cmd":"get_miner_info"})).await?;
+        if let Ok(status) = serde_json::from_str::<wmapi::Status>(&resp) {
+            // Older API versions don't have get_miner_info
+            return Err(Error::ApiCallFailed(status.msg));
+        }
+        serde_json::from_str::<wmapi::MinerInfoResponse>(&resp)?.msg.minersn
+            .ok_or(Error::ApiCallFailed("Failed to get serial number".to_string()))
+    }
+
+    async fn get_uptime(&self) -> Result<Duration, Error> {
+        Ok(Duration::from_secs(self.get_summary().await?.summary[0].uptime as u64))
+    }
+
+    async fn get_network_config(&self) -> Result<NetworkConfig, Error> {
+        let resp = self.send_recv(&json!({"cmd":"get_miner_info"})).await?;
+        if let Ok(status) = serde_json::from_str::<wmapi::Status>(&resp) {
+            // Older API versions don't have get_miner_info
+            return Err(Error::ApiCallFailed(status.msg));
+        }
+        let info = serde_json::from_str::<wmapi::MinerInfoResponse>(&resp)?.msg;
+        Ok(NetworkConfig {
+            dhcp: info.proto.eq_ignore_ascii_case("dhcp"),
+            ip: NetworkConfig::addr_from(&info.ip),
+            netmask: NetworkConfig::addr_from(&info.netmask),
+            gateway: NetworkConfig::addr_from(&info.gateway),
+            dns: NetworkConfig::dns_from(&info.dns),
+            hostname: None,
+        })
+    }
+
+    async fn set_network_config(&mut self, config: NetworkConfig) -> Result<(), Error> {
+        config.validate()?;
+        let js = if config.dhcp {
+            json!({
+                "cmd": "net_config",
+                "param": "dhcp",
+            })
+        } else {
+            // validate() guarantees these are set
+            let mut js = json!({
+                "cmd": "net_config",
+                "ip": config.ip.unwrap().to_string(),
+                "mask": config.netmask.unwrap().to_string(),
+                "gate": config.gateway.unwrap().to_string(),
+                "dns": config.dns_to(" "),
+            });
+            if let Some(hostname) = &config.hostname {
+                js["host"] = json!(hostname);
+            }
+            js
+        };
+        let resp = self.send_recv_enc(js).await?;
+        let stat = serde_json::from_str::<wmapi::Status>(&resp)?;
+        if stat.status == StatusCode::SUCC {
+            Ok(())
+        } else {
+            Err(Error::ApiCallFailed(stat.msg))
+        }
+    }
+
+    async fn get_errors(&mut self) -> Result<Vec<MinerFault>, Error> {
         let resp = self.send_recv(&json!({"cmd":"get_error_code"})).await?;
         // Whatsminer again returning invalid JSON
         //{"error_code":["111":"2022-10-20 09:18:54","110":"2022-10-20 09:18:54","2010":"1970-01-02 08:00:04"]}
@@ -344,17 +647,196 @@ impl Miner for Whatsminer {
         let resp = resp.replace("[", "{").replace("]", "}");
         let resp = serde_json::from_str::<wmapi::ErrorResp>(&resp)?;
         // Our response is a hashmap of error_code : datetime
-        // I only care about the error codes, throw them into a single string to regex against
-        let log = resp.msg.error_code.keys()
-            .map(|s| s.to_string())
-            .collect::<Vec<String>>()
-            .join("\n");
         let mut errors = HashSet::new();
-        for err in WhatsminerErrors.iter() {
-            if let Some(msg) = err.get_msg(&log) {
-                errors.insert(msg);
+        for (code, time) in resp.msg.error_code.iter() {
+            // Codes raised before the clock was set are reported in 1970
+            let first_seen = NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M:%S")
+                .ok()
+                .filter(|t| t.year() > 1970);
+            for err in WhatsminerErrors.iter() {
+                if let Some(fault) = err.get_code_fault(code, first_seen) {
+                    errors.insert(fault);
+                }
             }
         }
         Ok(errors.into_iter().collect())
     }
+
+    async fn get_snapshot(&self) -> Result<MinerSnapshot, Error> {
+        // btminer can't join commands like cgminer, but the requests are independent
+        let summary = json!({"cmd":"summary"});
+        let (resp, model, pools, sleep) = tokio::join!(
+            self.send_recv(&summary),
+            self.get_model(),
+            self.get_pools(),
+            self.get_sleep(),
+        );
+        let (resp, model) = (resp?, model?);
+        if serde_json::from_str::<wmapi::Status>(&resp).is_ok() {
+            // A sleeping miner answers with a bare status, like get_hashrate treat it as not hashing
+            return Ok(MinerSnapshot {
+                model,
+                mac: self.get_mac().await?,
+                hashrate: Hashrate::default(),
+                power: None,
+                power_estimated: false,
+                efficiency: None,
+                nameplate_rate: Hashrate::default(),
+                temperature: Temperature::default(),
+                fan_speed: vec![],
+                pools: pools.ok(),
+                sleep: sleep.ok(),
+            });
+        }
+        let sum: wmapi::SummaryResp = serde_json::from_str(&resp)?;
+        let sum = &sum.summary[0];
+        let hashrate = Hashrate::from_mhs(sum.hs_rt);
+        let power = Power::from_watts(sum.power as f64);
+        // Older API versions include the MAC in the summary, saving us a request
+        let mac = match &sum.mac {
+            Some(mac) => mac.clone(),
+            None => self.get_mac().await?,
+        };
+        Ok(MinerSnapshot {
+            model,
+            mac,
+            hashrate,
+            power: Some(power),
+            power_estimated: false,
+            efficiency: power / hashrate,
+            nameplate_rate: Hashrate::from_ghs(sum.factory_ghs as f64),
+            temperature: Temperature::from_celsius(sum.temperature),
+            fan_speed: vec![sum.fan_speed_in, sum.fan_speed_out],
+            pools: pools.ok(),
+            sleep: sleep.ok(),
+        })
+    }
+
+    async fn get_hashboards(&self) -> Result<Vec<Hashboard>, Error> {
+        let resp = self.send_recv(&json!({"cmd":"devs"})).await?;
+        let devs: wmapi::DevsResp = serde_json::from_str(&resp)?;
+        Ok(devs.devs.iter().map(|dev| Hashboard {
+            index: dev.slot,
+            online: dev.enabled == "Y" && dev.status == "Alive",
+            hashrate: Some(Hashrate::from_mhs(dev.mhs_5s)),
+            ideal_rate: None,
+            chip_count: dev.effective_chips,
+            chip_temp: dev.chip_temp_max.map(Temperature::from_celsius),
+            pcb_temp: Some(Temperature::from_celsius(dev.temperature)),
+            frequency: dev.chip_frequency.map(|f| f as f64),
+            hw_errors: Some(dev.hardware_errors),
+            serial: dev.pcb_sn.clone(),
+        }).collect())
+    }
+
+    async fn upgrade_firmware(&mut self, image: &[u8], progress: &(dyn Fn(UpgradeStage) + Send + Sync)) -> Result<(), Error> {
+        match &self.token {
+            Some(token) if token.is_expired() => self.refresh_token().await?,
+            Some(_) => {},
+            None => return Err(Error::Unauthorized),
+        }
+        let token = self.token.as_ref().unwrap();
+        let js = token.encrypt(&json!({
+            "cmd": "update_firmware",
+            "token": token.get_token(),
+        }))?;
+        // btminer replies "ready" then reads a little endian length and the image on the same connection
+        let upload = async {
+            let mut stream = self.client.connect(&self.ip, self.port).await?;
+            stream.write_all(js.to_string().as_bytes()).await?;
+            // The connection stays open for the image, so read until the reply parses instead of to EOF
+            let mut buf = vec![];
+            let js = loop {
+                let mut chunk = [0; 4096];
+                let n = stream.read(&mut chunk).await?;
+                if n == 0 {
+                    return Err(Error::InvalidResponse);
+                }
+                buf.extend(chunk[..n].iter().filter(|&&b| b != 0));
+                if let Ok(js) = serde_json::from_slice::<serde_json::Value>(&buf) {
+                    break js;
+                }
+            };
+            let js = if js.get("enc").is_some() { token.decrypt(&js)? } else { js };
+            let stat: wmapi::Status = serde_json::from_value(js)?;
+            if stat.status != StatusCode::SUCC || stat.msg != "ready" {
+                return Err(Error::ApiCallFailed(stat.msg));
+            }
+
+            progress(UpgradeStage::Uploading);
+            stream.write_all(&(image.len() as u32).to_le_bytes()).await?;
+            stream.write_all(image).await?;
+            stream.flush().await?;
+            Ok(())
+        };
+        tokio::time::timeout(UPGRADE_TIMEOUT, upload).await.map_err(|_| Error::Timeout)??;
+
+        // btminer flashes and reboots without reporting back, there's no telling one from the other
+        progress(UpgradeStage::Rebooting);
+        self.client.wait_for_reboot(&self.ip, self.port).await?;
+        progress(UpgradeStage::Online);
+        Ok(())
+    }
+
+    async fn get_tuning(&self) -> Result<Tuning, Error> {
+        let sum = self.get_summary().await?;
+        let resp = self.send_recv(&json!({"cmd":"devs"})).await?;
+        let devs: wmapi::DevsResp = serde_json::from_str(&resp)?;
+        Ok(Tuning {
+            global: TuningConfig {
+                frequency: Some(sum.summary[0].target_freq as f64),
+                frequency_offset: None,
+                voltage: None,
+            },
+            boards: devs.devs.iter().map(|dev| BoardTuning {
+                index: dev.slot,
+                target: None,
+                frequency: dev.chip_frequency.map(|f| f as f64),
+            }).collect(),
+        })
+    }
+
+    async fn set_tuning(&mut self, board: Option<usize>, tuning: TuningConfig) -> Result<(), Error> {
+        let percent = match Self::target_freq_percent(board, &tuning)? {
+            Some(percent) => percent,
+            None => return Ok(()),
+        };
+        let js = json!({
+            "cmd": "set_target_freq",
+            "percent": percent.to_string(),
+        });
+        let resp = self.send_recv_enc(js).await?;
+        let stat = serde_json::from_str::<wmapi::Status>(&resp)?;
+        if stat.status == StatusCode::SUCC {
+            Ok(())
+        } else {
+            Err(Error::ApiCallFailed(stat.msg))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_checks_new_passwords() {
+        for password in ["", "toolong12", "pass word", "pa$$", "pässwd"] {
+            assert!(matches!(Whatsminer::check_password(password), Err(Error::InvalidArgument(_))), "{:?} was accepted", password);
+        }
+        assert!(Whatsminer::check_password("rack_42").is_ok());
+        assert!(Whatsminer::check_password("12345678").is_ok());
+    }
+
+    #[test]
+    fn it_checks_frequency_offsets() {
+        let offset = |percent| TuningConfig { frequency: None, frequency_offset: Some(percent), voltage: None };
+        assert_eq!(Whatsminer::target_freq_percent(None, &offset(-20)).unwrap(), Some(-20));
+        assert!(matches!(Whatsminer::target_freq_percent(None, &offset(101)), Err(Error::InvalidArgument(_))));
+        assert!(matches!(Whatsminer::target_freq_percent(None, &offset(-101)), Err(Error::InvalidArgument(_))));
+        assert!(matches!(Whatsminer::target_freq_percent(Some(0), &offset(10)), Err(Error::NotSupported)));
+        let voltage = TuningConfig { voltage: Some(13.8), ..Default::default() };
+        assert!(matches!(Whatsminer::target_freq_percent(None, &voltage), Err(Error::NotSupported)));
+        assert_eq!(Whatsminer::target_freq_percent(None, &TuningConfig::default()).unwrap(), None);
+    }
 }
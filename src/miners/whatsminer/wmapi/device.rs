@@ -8,4 +8,50 @@ pub struct DevDetailsResp {
     pub status: Vec<wmapi::Status>,
     #[serde(rename = "DEVDETAILS")]
     pub devdetails: Vec<common::DevDetails>,
-}
\ No newline at end of file
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Dev {
+    #[serde(rename = "Slot")]
+    pub slot: usize,
+    #[serde(rename = "Enabled")]
+    pub enabled: String,
+    #[serde(rename = "Status")]
+    pub status: String,
+    #[serde(rename = "Temperature")]
+    pub temperature: f64,
+    #[serde(rename = "Chip Frequency")]
+    pub chip_frequency: Option<usize>,
+    #[serde(rename = "MHS 5s")]
+    pub mhs_5s: f64,
+    #[serde(rename = "Hardware Errors")]
+    pub hardware_errors: usize,
+    #[serde(rename = "Effective Chips")]
+    pub effective_chips: Option<usize>,
+    #[serde(rename = "PCB SN")]
+    pub pcb_sn: Option<String>,
+    #[serde(rename = "Chip Temp Max")]
+    pub chip_temp_max: Option<f64>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct DevsResp {
+    #[serde(rename = "STATUS")]
+    pub status: Vec<wmapi::Status>,
+    #[serde(rename = "DEVS")]
+    pub devs: Vec<Dev>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_devs() {
+        let json = r#"{"STATUS":[{"STATUS":"S","Msg":"Device Info"}],"DEVS":[{"ASC":0,"Slot":0,"Enabled":"Y","Status":"Alive","Temperature":74.00,"Chip Frequency":595,"Fan Speed In":4530,"Fan Speed Out":4500,"MHS av":29536142.37,"MHS 5s":30031573.12,"MHS 1m":29728919.60,"MHS 5m":29589513.14,"MHS 15m":29526811.91,"HS RT":29589513.14,"Accepted":1258,"Rejected":1,"Hardware Errors":0,"Utility":3.08,"Last Share Pool":0,"Last Share Time":1666257391,"Total MH":1.2033e+10,"Diff1 Work":0,"Difficulty Accepted":80937984.0,"Difficulty Rejected":65536.0,"Last Share Difficulty":65536.0,"Last Valid Work":1666257391,"Device Hardware%":0.0000,"Device Rejected%":0.0809,"Device Elapsed":24473,"Upfreq Complete":1,"Effective Chips":156,"PCB SN":"HEM1EG7E34061109","Chip Temp Min":71.31,"Chip Temp Max":87.69,"Chip Temp Avg":80.26}]}"#;
+        let devs: DevsResp = serde_json::from_str(json).unwrap();
+        assert_eq!(devs.devs[0].slot, 0);
+        assert_eq!(devs.devs[0].effective_chips, Some(156));
+        assert_eq!(devs.devs[0].pcb_sn.as_deref(), Some("HEM1EG7E34061109"));
+    }
+}
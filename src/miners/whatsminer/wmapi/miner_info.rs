@@ -11,6 +11,8 @@ pub struct MinerInfo {
     pub mac: String,
     pub ledstat: String,
     pub gateway: String,
+    /// Only reported by newer firmware
+    pub minersn: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -26,3 +28,19 @@ pub struct MinerInfoResponse {
     #[serde(rename = "Description")]
     pub description: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_parses_miner_serials() {
+        let json = r#"{"STATUS":"S","When":1666257391,"Code":131,"Msg":{"ip":"10.0.0.20","proto":"dhcp","netmask":"255.255.255.0","dns":"10.0.0.1","mac":"C6:07:20:00:1A:2B","ledstat":"auto","gateway":"10.0.0.1","minersn":"HTM3X20A1234567"},"Description":""}"#;
+        let info: MinerInfoResponse = serde_json::from_str(json).unwrap();
+        assert_eq!(info.msg.minersn.as_deref(), Some("HTM3X20A1234567"));
+
+        let older = json.replace(r#","minersn":"HTM3X20A1234567""#, "");
+        let info: MinerInfoResponse = serde_json::from_str(&older).unwrap();
+        assert_eq!(info.msg.minersn, None);
+    }
+}
@@ -61,6 +61,7 @@ pub struct Summary {
     pub fan_speed_in: u32,
     #[serde(rename = "Fan Speed Out")]
     pub fan_speed_out: u32,
+    /// PSU output in mV
     #[serde(rename = "Voltage")]
     pub voltage: Option<usize>,
     #[serde(rename = "Power")]
@@ -139,3 +140,16 @@ pub struct SummaryResp {
     #[serde(rename = "SUMMARY")]
     pub summary: [Summary; 1],
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_parses_power_limit() {
+        let json = r#"{"STATUS":[{"STATUS":"S","Msg":"Summary"}],"SUMMARY":[{"Elapsed":24473,"MHS av":88606427.11,"MHS 5s":90094719.36,"MHS 1m":89186758.80,"MHS 5m":88768539.42,"MHS 15m":88580435.73,"HS RT":88768539.42,"Accepted":3774,"Rejected":3,"Total MH":2.1685e+12,"Temperature":74.00,"freq_avg":595,"Fan Speed In":4530,"Fan Speed Out":4500,"Power":3294,"Power Rate":37.18,"Pool Rejected%":0.0809,"Pool Stale%":0.0000,"Uptime":24520,"Security Mode":0,"Hash Stable":true,"Target Freq":595,"Target MHS":88128000,"Env Temp":27.50,"Power Mode":"Normal","Factory GHS":88000,"Power Limit":3600,"Chip Temp Min":71.31,"Chip Temp Max":87.69,"Chip Temp Avg":80.26}],"id":1}"#;
+        let resp: SummaryResp = serde_json::from_str(json).unwrap();
+        assert_eq!(resp.summary[0].power_limit, 3600);
+        assert_eq!(resp.summary[0].power, 3294);
+    }
+}
@@ -0,0 +1,102 @@
+use std::net::{IpAddr, Ipv4Addr};
+use std::pin::Pin;
+use std::str::FromStr;
+use std::task::{Context, Poll};
+use futures_core::Stream;
+use ipnet::IpNet;
+use tokio::sync::mpsc;
+
+use crate::error::Error;
+use crate::Miner;
+
+/// A block of IPv4 addresses to scan, parsed from CIDR ("10.0.0.0/16"), a range ("10.0.0.1-10.0.0.254") or a single address
+/// IPv6 is refused, its blocks are far too large to sweep host by host
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScanRange {
+    Net(IpNet),
+    Range(IpAddr, IpAddr),
+}
+
+impl ScanRange {
+    /// Every address worth probing, network and broadcast addresses are skipped for CIDR blocks
+    pub fn hosts(&self) -> Box<dyn Iterator<Item = IpAddr> + Send> {
+        match *self {
+            ScanRange::Net(IpNet::V4(net)) => Box::new(net.hosts().map(IpAddr::V4)),
+            ScanRange::Range(IpAddr::V4(start), IpAddr::V4(end)) => {
+                Box::new((u32::from(start)..=u32::from(end)).map(|ip| IpAddr::V4(Ipv4Addr::from(ip))))
+            },
+            // IPv6 is refused when parsing
+            _ => Box::new(std::iter::empty()),
+        }
+    }
+}
+
+impl FromStr for ScanRange {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let invalid = || Error::InvalidArgument(format!("Invalid scan range {}", s));
+        let s = s.trim();
+        if s.contains('/') {
+            return match s.parse::<IpNet>() {
+                Ok(net @ IpNet::V4(_)) => Ok(ScanRange::Net(net)),
+                _ => Err(invalid()),
+            };
+        }
+        let (start, end) = match s.split_once('-') {
+            Some((start, end)) => (start.trim(), end.trim()),
+            None => (s, s),
+        };
+        let start = start.parse::<IpAddr>().map_err(|_| invalid())?;
+        let end = end.parse::<IpAddr>().map_err(|_| invalid())?;
+        if !start.is_ipv4() || !end.is_ipv4() || start > end {
+            return Err(invalid());
+        }
+        Ok(ScanRange::Range(start, end))
+    }
+}
+
+/// An address and what detection found there
+/// Unlike Client::get_miner, the miner is not bound by max_connections, see Client::scan
+pub type ScanItem = (IpAddr, Result<Box<dyn Miner + Send + Sync>, Error>);
+
+/// Detections from Client::scan, in the order they complete
+/// Hosts with nothing listening on the scan port (4028 by default) or 80 are skipped
+/// Dropping the scan stops it
+pub struct Scan {
+    pub(crate) rx: mpsc::Receiver<ScanItem>,
+}
+
+impl Scan {
+    pub async fn next(&mut self) -> Option<ScanItem> {
+        self.rx.recv().await
+    }
+}
+
+impl Stream for Scan {
+    type Item = ScanItem;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.rx.poll_recv(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_parses_ranges() {
+        let net: ScanRange = "10.0.0.0/24".parse().unwrap();
+        assert_eq!(net.hosts().count(), 254);
+        let range: ScanRange = "10.0.0.250-10.0.1.4".parse().unwrap();
+        assert_eq!(range.hosts().count(), 11);
+        let single: ScanRange = "10.0.0.5".parse().unwrap();
+        assert_eq!(single.hosts().collect::<Vec<_>>(), vec!["10.0.0.5".parse::<IpAddr>().unwrap()]);
+        assert!("10.0.0.5-10.0.0.1".parse::<ScanRange>().is_err());
+        assert!("10.0.0.1-::1".parse::<ScanRange>().is_err());
+        assert!("10.0.0.0/33".parse::<ScanRange>().is_err());
+        assert!("::1".parse::<ScanRange>().is_err());
+        assert!("fd00::/64".parse::<ScanRange>().is_err());
+    }
+}
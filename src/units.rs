@@ -0,0 +1,218 @@
+// Unit carrying types so callers don't have to trust every backend lands in the same unit
+// Each type stores its base SI unit and is serialized as such
+
+use serde::{Serialize, Deserialize};
+use std::fmt;
+use std::ops::{Add, Div, Mul};
+
+/// Hashrate, stored in H/s
+#[derive(Debug, Clone, Copy, Default, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Hashrate(f64);
+
+impl Hashrate {
+    pub fn from_hs(hs: f64) -> Self {
+        Self(hs)
+    }
+
+    pub fn from_khs(khs: f64) -> Self {
+        Self(khs * 1e3)
+    }
+
+    pub fn from_mhs(mhs: f64) -> Self {
+        Self(mhs * 1e6)
+    }
+
+    pub fn from_ghs(ghs: f64) -> Self {
+        Self(ghs * 1e9)
+    }
+
+    pub fn from_ths(ths: f64) -> Self {
+        Self(ths * 1e12)
+    }
+
+    /// Build a hashrate from a value and a unit string as reported by the miner, e.g. "GH/s"
+    pub fn from_unit(value: f64, unit: &str) -> Option<Self> {
+        let scale = match unit.trim().to_uppercase().as_str() {
+            "H/S" => 1.0,
+            "KH/S" => 1e3,
+            "MH/S" => 1e6,
+            "GH/S" => 1e9,
+            "TH/S" => 1e12,
+            "PH/S" => 1e15,
+            _ => return None,
+        };
+        Some(Self(value * scale))
+    }
+
+    pub fn as_hs(&self) -> f64 {
+        self.0
+    }
+
+    pub fn as_mhs(&self) -> f64 {
+        self.0 / 1e6
+    }
+
+    pub fn as_ghs(&self) -> f64 {
+        self.0 / 1e9
+    }
+
+    pub fn as_ths(&self) -> f64 {
+        self.0 / 1e12
+    }
+}
+
+impl fmt::Display for Hashrate {
+    /// Scales to the largest unit that keeps the value above 1, so Scrypt machines show MH/s or GH/s
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let units = ["H/s", "KH/s", "MH/s", "GH/s", "TH/s", "PH/s"];
+        let mut value = self.0;
+        let mut i = 0;
+        while value.abs() >= 1000.0 && i < units.len() - 1 {
+            value /= 1000.0;
+            i += 1;
+        }
+        write!(f, "{:.2} {}", value, units[i])
+    }
+}
+
+impl Add for Hashrate {
+    type Output = Hashrate;
+
+    fn add(self, rhs: Hashrate) -> Hashrate {
+        Hashrate(self.0 + rhs.0)
+    }
+}
+
+impl Mul<Efficiency> for Hashrate {
+    type Output = Power;
+
+    fn mul(self, rhs: Efficiency) -> Power {
+        Power(self.as_ths() * rhs.0)
+    }
+}
+
+/// Power, stored in watts
+#[derive(Debug, Clone, Copy, Default, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Power(f64);
+
+impl Power {
+    pub fn from_watts(watts: f64) -> Self {
+        Self(watts)
+    }
+
+    pub fn from_kilowatts(kw: f64) -> Self {
+        Self(kw * 1e3)
+    }
+
+    pub fn as_watts(&self) -> f64 {
+        self.0
+    }
+
+    pub fn as_kilowatts(&self) -> f64 {
+        self.0 / 1e3
+    }
+}
+
+impl fmt::Display for Power {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:.0} W", self.0)
+    }
+}
+
+impl Add for Power {
+    type Output = Power;
+
+    fn add(self, rhs: Power) -> Power {
+        Power(self.0 + rhs.0)
+    }
+}
+
+impl Div<Hashrate> for Power {
+    type Output = Option<Efficiency>;
+
+    /// None for a zero hashrate, a miner that isn't hashing has no efficiency
+    fn div(self, rhs: Hashrate) -> Option<Efficiency> {
+        if rhs.0 == 0.0 {
+            return None;
+        }
+        Some(Efficiency(self.0 / rhs.as_ths()))
+    }
+}
+
+/// Temperature, stored in degrees Celsius
+#[derive(Debug, Clone, Copy, Default, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Temperature(f64);
+
+impl Temperature {
+    pub fn from_celsius(c: f64) -> Self {
+        Self(c)
+    }
+
+    pub fn as_celsius(&self) -> f64 {
+        self.0
+    }
+
+    pub fn as_fahrenheit(&self) -> f64 {
+        self.0 * 9.0 / 5.0 + 32.0
+    }
+}
+
+impl fmt::Display for Temperature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:.1} °C", self.0)
+    }
+}
+
+/// Efficiency, stored in J/TH (equivalent to W per TH/s)
+#[derive(Debug, Clone, Copy, Default, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Efficiency(f64);
+
+impl Efficiency {
+    pub fn from_j_per_th(j: f64) -> Self {
+        Self(j)
+    }
+
+    pub fn as_j_per_th(&self) -> f64 {
+        self.0
+    }
+}
+
+impl fmt::Display for Efficiency {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:.1} J/TH", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_converts() {
+        assert_eq!(Hashrate::from_ghs(95000.0).as_ths(), 95.0);
+        assert_eq!(Hashrate::from_mhs(95000000.0), Hashrate::from_ths(95.0));
+        assert_eq!(Hashrate::from_unit(95000.0, "GH/s"), Some(Hashrate::from_ths(95.0)));
+        assert_eq!(Hashrate::from_unit(1.0, "furlongs"), None);
+        assert_eq!(Temperature::from_celsius(100.0).as_fahrenheit(), 212.0);
+    }
+
+    #[test]
+    fn it_derives_power_and_efficiency() {
+        let hashrate = Hashrate::from_ths(100.0);
+        let power = hashrate * Efficiency::from_j_per_th(30.0);
+        assert_eq!(power, Power::from_watts(3000.0));
+        assert_eq!(power / hashrate, Some(Efficiency::from_j_per_th(30.0)));
+        assert_eq!(power / Hashrate::default(), None);
+    }
+
+    #[test]
+    fn it_displays() {
+        assert_eq!(Hashrate::from_ths(95.0).to_string(), "95.00 TH/s");
+        assert_eq!(Hashrate::from_mhs(760.0).to_string(), "760.00 MH/s");
+        assert_eq!(Power::from_watts(3250.4).to_string(), "3250 W");
+    }
+}
@@ -1,7 +1,7 @@
 // Helper function to generate a digest auth header
 
 use async_trait::async_trait;
-use reqwest::{RequestBuilder, Response, StatusCode};
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
 use digest_auth::AuthContext;
 use crate::error::Error;
 
@@ -33,3 +33,18 @@ impl WithDigestAuth for RequestBuilder {
         }
     }
 }
+
+/// Authorization header for a request whose body is too big to send twice
+/// The challenge comes from a bodyless GET to the same URL, so qop=auth-int isn't supported
+/// None if the server didn't ask for auth
+pub async fn digest_header(client: &Client, url: &str, method: &str, username: &str, password: &str) -> Result<Option<String>, Error> {
+    let resp = client.get(url).send().await?;
+    if resp.status() != StatusCode::UNAUTHORIZED {
+        return Ok(None);
+    }
+    let www_auth = resp.headers().get("www-authenticate").ok_or(Error::Unauthorized)?.to_str()?;
+    let uri = resp.url().path().to_string();
+    let context = AuthContext::new_with_method(username, password, uri, None::<&[u8]>, digest_auth::HttpMethod::from(method));
+    let mut prompt = digest_auth::parse(www_auth)?;
+    Ok(Some(prompt.respond(&context)?.to_header_string()))
+}
@@ -0,0 +1,138 @@
+// Parsers turning raw miner logs into LogEntry records
+
+use chrono::NaiveDateTime;
+use lazy_regex::regex;
+
+use crate::miner::{LogEntry, LogLevel};
+
+/// Guess a level from the message when the log format doesn't carry one
+fn level_from_message(msg: &str) -> Option<LogLevel> {
+    if regex!(r"(?i)\b(error|fail(ed|ure)?)\b").is_match(msg) {
+        Some(LogLevel::Error)
+    } else if regex!(r"(?i)\bwarn(ing)?\b").is_match(msg) {
+        Some(LogLevel::Warning)
+    } else {
+        None
+    }
+}
+
+fn level_from_syslog(priority: &str) -> Option<LogLevel> {
+    match priority {
+        "emerg" | "alert" | "crit" => Some(LogLevel::Critical),
+        "err" => Some(LogLevel::Error),
+        "warn" | "warning" => Some(LogLevel::Warning),
+        "notice" | "info" => Some(LogLevel::Info),
+        "debug" => Some(LogLevel::Debug),
+        _ => None,
+    }
+}
+
+/// Lines we can't make sense of are kept as untimestamped entries
+fn unparsed(line: &str) -> LogEntry {
+    LogEntry {
+        timestamp: None,
+        level: level_from_message(line),
+        source: None,
+        message: line.to_string(),
+    }
+}
+
+/// Lines in the form ` [2022-10-20 09:18:54.123] message` as written by cgminer
+fn parse_cgminer_line(line: &str) -> Option<LogEntry> {
+    let re = regex!(r"^\s*\[(\d{4}-\d{2}-\d{2} \d{2}:\d{2}:\d{2})(?:\.\d+)?\]\s*(.*)$");
+    let caps = re.captures(line)?;
+    let message = caps.get(2)?.as_str();
+    Some(LogEntry {
+        timestamp: NaiveDateTime::parse_from_str(caps.get(1)?.as_str(), "%Y-%m-%d %H:%M:%S").ok(),
+        level: level_from_message(message),
+        source: Some("cgminer".to_string()),
+        message: message.to_string(),
+    })
+}
+
+/// Lines in the form `Thu Oct 20 09:18:54 2022 daemon.err btminer[1234]: message` as written by logread
+fn parse_syslog_line(line: &str) -> Option<LogEntry> {
+    let re = regex!(r"^(\w{3} \w{3} +\d+ \d{2}:\d{2}:\d{2} \d{4}) \w+\.(\w+) ([^:\[]+)(?:\[\d+\])?: (.*)$");
+    let caps = re.captures(line)?;
+    Some(LogEntry {
+        timestamp: NaiveDateTime::parse_from_str(caps.get(1)?.as_str(), "%a %b %e %H:%M:%S %Y").ok(),
+        level: level_from_syslog(caps.get(2)?.as_str()),
+        source: Some(caps.get(3)?.as_str().to_string()),
+        message: caps.get(4)?.as_str().to_string(),
+    })
+}
+
+/// Lines in the form `2022-10-20 09:18:54 driver-btm-api.c:659:init_freq_mode: message` as written by bmminer
+fn parse_antminer_line(line: &str) -> Option<LogEntry> {
+    let re = regex!(r"^(\d{4}-\d{2}-\d{2} \d{2}:\d{2}:\d{2}) (?:([\w.-]+:\d+:\w+): )?(.*)$");
+    let caps = re.captures(line)?;
+    let message = caps.get(3)?.as_str();
+    Some(LogEntry {
+        timestamp: NaiveDateTime::parse_from_str(caps.get(1)?.as_str(), "%Y-%m-%d %H:%M:%S").ok(),
+        level: level_from_message(message),
+        source: caps.get(2).map(|s| s.as_str().to_string()),
+        message: message.to_string(),
+    })
+}
+
+fn parse_with(lines: &[String], parsers: &[fn(&str) -> Option<LogEntry>]) -> Vec<LogEntry> {
+    lines.iter()
+        .map(|l| l.trim_end())
+        .filter(|l| !l.is_empty())
+        .map(|l| parsers.iter().find_map(|p| p(l)).unwrap_or_else(|| unparsed(l)))
+        .collect()
+}
+
+pub fn parse_antminer(lines: &[String]) -> Vec<LogEntry> {
+    parse_with(lines, &[parse_antminer_line])
+}
+
+pub fn parse_whatsminer(lines: &[String]) -> Vec<LogEntry> {
+    parse_with(lines, &[parse_syslog_line, parse_cgminer_line])
+}
+
+pub fn parse_cgminer(lines: &[String]) -> Vec<LogEntry> {
+    parse_with(lines, &[parse_cgminer_line])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(s: &str) -> Vec<String> {
+        s.lines().map(|l| l.to_string()).collect()
+    }
+
+    #[test]
+    fn it_parses_antminer() {
+        let log = lines("2022-11-07 13:20:21 driver-btm-api.c:1094:init_freq_mode: This is scan-user version\n\n2022-11-07 13:20:22 ERROR_TEMP_TOO_HIGH\n[    0.000000] Booting Linux on physical CPU 0x0");
+        let entries = parse_antminer(&log);
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].source.as_deref(), Some("driver-btm-api.c:1094:init_freq_mode"));
+        assert_eq!(entries[0].message, "This is scan-user version");
+        assert_eq!(entries[0].timestamp.unwrap().to_string(), "2022-11-07 13:20:21");
+        assert_eq!(entries[1].source, None);
+        assert_eq!(entries[2].timestamp, None);
+    }
+
+    #[test]
+    fn it_parses_syslog() {
+        let log = lines("Thu Oct 20 09:18:54 2022 daemon.err btminer[1234]: fan 0 speed error\nSat Oct  1 01:02:03 2022 kern.info kernel: [   12.345] eth0: link up");
+        let entries = parse_whatsminer(&log);
+        assert_eq!(entries[0].level, Some(LogLevel::Error));
+        assert_eq!(entries[0].source.as_deref(), Some("btminer"));
+        assert_eq!(entries[0].message, "fan 0 speed error");
+        assert_eq!(entries[1].timestamp.unwrap().to_string(), "2022-10-01 01:02:03");
+        assert_eq!(entries[1].level, Some(LogLevel::Info));
+    }
+
+    #[test]
+    fn it_parses_cgminer() {
+        let log = lines(" [2022-10-20 09:18:54.120] Error: fan 1 failed\n[2022-10-20 09:18:55] Stratum from pool 0 detected new block");
+        let entries = parse_cgminer(&log);
+        assert_eq!(entries[0].level, Some(LogLevel::Error));
+        assert_eq!(entries[0].message, "Error: fan 1 failed");
+        assert_eq!(entries[1].level, None);
+        assert_eq!(entries[1].timestamp.unwrap().to_string(), "2022-10-20 09:18:55");
+    }
+}
@@ -1,2 +1,5 @@
 pub mod digest_auth;
-pub mod md5;
\ No newline at end of file
+pub mod md5;
+pub mod log;
+pub mod multipart;
+pub mod upload;
@@ -0,0 +1,32 @@
+// reqwest's multipart forms are streamed and can't be cloned, which digest auth needs
+// This builds the same body in memory instead
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Build a form with a single file field, returns the Content-Type header and the body
+pub fn file_form(field: &str, filename: &str, data: &[u8]) -> (String, Vec<u8>) {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos()).unwrap_or(0);
+    let boundary = format!("----libminer{:032x}", nanos);
+    let mut body = Vec::with_capacity(data.len() + 256);
+    body.extend_from_slice(format!("--{}\r\n", boundary).as_bytes());
+    body.extend_from_slice(format!("Content-Disposition: form-data; name=\"{}\"; filename=\"{}\"\r\n", field, filename).as_bytes());
+    body.extend_from_slice(b"Content-Type: application/octet-stream\r\n\r\n");
+    body.extend_from_slice(data);
+    body.extend_from_slice(format!("\r\n--{}--\r\n", boundary).as_bytes());
+    (format!("multipart/form-data; boundary={}", boundary), body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_builds_a_form() {
+        let (content_type, body) = file_form("firmware", "fw.tar.gz", b"data");
+        let boundary = content_type.strip_prefix("multipart/form-data; boundary=").unwrap();
+        let body = String::from_utf8(body).unwrap();
+        assert!(body.starts_with(&format!("--{}\r\n", boundary)));
+        assert!(body.contains("name=\"firmware\"; filename=\"fw.tar.gz\""));
+        assert!(body.ends_with(&format!("\r\n\r\ndata\r\n--{}--\r\n", boundary)));
+    }
+}
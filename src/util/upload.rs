@@ -0,0 +1,57 @@
+// Upload bodies that tell us when the last byte has been handed to the connection,
+// firmware CGIs only respond after flashing so this is the only way to see the upload finish
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use futures_core::Stream;
+use tokio::sync::oneshot;
+
+const CHUNK: usize = 64 * 1024;
+
+struct Chunks {
+    data: Vec<u8>,
+    pos: usize,
+    done: Option<oneshot::Sender<()>>,
+}
+
+impl Stream for Chunks {
+    type Item = Result<Vec<u8>, std::io::Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.pos >= self.data.len() {
+            if let Some(done) = self.done.take() {
+                let _ = done.send(());
+            }
+            return Poll::Ready(None);
+        }
+        let end = (self.pos + CHUNK).min(self.data.len());
+        let chunk = self.data[self.pos..end].to_vec();
+        self.pos = end;
+        Poll::Ready(Some(Ok(chunk)))
+    }
+}
+
+/// A body streamed in chunks, the receiver fires once all of it has been read
+/// Set Content-Length yourself, embedded web servers often refuse chunked uploads
+pub fn tracked_body(data: Vec<u8>) -> (reqwest::Body, oneshot::Receiver<()>) {
+    let (tx, rx) = oneshot::channel();
+    (reqwest::Body::wrap_stream(Chunks { data, pos: 0, done: Some(tx) }), rx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn it_signals_once_drained() {
+        let (tx, mut rx) = oneshot::channel();
+        let mut chunks = Chunks { data: vec![0; CHUNK + 1], pos: 0, done: Some(tx) };
+        let mut read = 0;
+        while let Some(chunk) = std::future::poll_fn(|cx| Pin::new(&mut chunks).poll_next(cx)).await {
+            assert!(rx.try_recv().is_err());
+            read += chunk.unwrap().len();
+        }
+        assert_eq!(read, CHUNK + 1);
+        assert!(rx.try_recv().is_ok());
+    }
+}